@@ -1,5 +1,13 @@
-mod map;
-mod set;
+#[cfg(test)]
+extern crate quickcheck;
+#[cfg(test)]
+#[macro_use(quickcheck)]
+extern crate quickcheck_macros;
+
+pub mod map;
+pub mod set;
+#[cfg(test)]
+mod testing;
 mod tree;
 
 pub use map::PrefixMap;
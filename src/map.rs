@@ -1,16 +1,23 @@
-use crate::tree::Tree;
+//! A map implemented with prefix tree.
+
+use crate::tree::{common_prefix, DiffIter, SetOp, Tree};
 use std::hash::{Hash, Hasher};
 use std::iter::{FromIterator, FusedIterator};
-use std::ops::Index;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::{Bound, Index, RangeBounds};
+use std::slice;
+use std::vec;
 
 /// A map implemented with prefix tree.
+///
+/// Keys are sequences of `K` and are kept in lexicographic order.
 #[derive(Debug, Clone, Default)]
 pub struct PrefixMap<K, V> {
     root: Tree<K, V>,
-    length: usize,
 }
 
-impl<K: Eq + Clone, V> PrefixMap<K, V> {
+impl<K: Ord + Clone, V> PrefixMap<K, V> {
     /// Creates an empty `PrefixMap`.
     ///
     /// # Examples
@@ -23,7 +30,6 @@ impl<K: Eq + Clone, V> PrefixMap<K, V> {
     pub fn new() -> PrefixMap<K, V> {
         PrefixMap {
             root: Tree::empty(),
-            length: 0,
         }
     }
 
@@ -62,6 +68,59 @@ impl<K: Eq + Clone, V> PrefixMap<K, V> {
         *self = PrefixMap::new();
     }
 
+    /// Clears the map, returning all key-value pairs as an iterator, in
+    /// lexicographic order. The map is empty afterwards even if the iterator
+    /// is dropped before being fully consumed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("b", 2);
+    /// map.insert("a", 1);
+    ///
+    /// let drained: Vec<_> = map.drain().collect();
+    /// assert_eq!(drained, vec![(b"a".to_vec(), 1), (b"b".to_vec(), 2)]);
+    /// assert!(map.is_empty());
+    /// ```
+    pub fn drain(&mut self) -> Drain<'_, K, V> {
+        Drain {
+            inner: mem::replace(self, PrefixMap::new()).into_iter(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Retains only the entries for which the predicate returns `true`,
+    /// visiting them in lexicographic order. Nodes left without values are
+    /// pruned as it goes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("ab", 2);
+    /// map.insert("abc", 3);
+    ///
+    /// map.retain(|key, value| {
+    ///     *value *= 10;
+    ///     key.len() != 2
+    /// });
+    /// assert_eq!(map.get("a"), Some(&10));
+    /// assert_eq!(map.get("ab"), None);
+    /// assert_eq!(map.get("abc"), Some(&30));
+    /// ```
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&[K], &mut V) -> bool,
+    {
+        self.root.retain(&mut vec![], &mut f)
+    }
+
     /// Returns a reference to the value corresponding to the key.
     ///
     /// # Examples
@@ -81,6 +140,60 @@ impl<K: Eq + Clone, V> PrefixMap<K, V> {
         self.root.find(key.as_ref()).and_then(|x| x.value())
     }
 
+    /// Returns the key stored in the map which is equal to the given key,
+    /// along with its value.
+    ///
+    /// The stored key can differ from the given one when the ordering of
+    /// `K` treats distinct elements as equal.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    /// use std::cmp::Ordering;
+    ///
+    /// #[derive(Clone, Debug, Eq)]
+    /// struct NoCase(u8);
+    ///
+    /// impl PartialEq for NoCase {
+    ///     fn eq(&self, other: &NoCase) -> bool {
+    ///         self.0.eq_ignore_ascii_case(&other.0)
+    ///     }
+    /// }
+    ///
+    /// impl PartialOrd for NoCase {
+    ///     fn partial_cmp(&self, other: &NoCase) -> Option<Ordering> {
+    ///         Some(self.cmp(other))
+    ///     }
+    /// }
+    ///
+    /// impl Ord for NoCase {
+    ///     fn cmp(&self, other: &NoCase) -> Ordering {
+    ///         self.0.to_ascii_lowercase().cmp(&other.0.to_ascii_lowercase())
+    ///     }
+    /// }
+    ///
+    /// let word = |s: &str| s.bytes().map(NoCase).collect::<Vec<_>>();
+    /// let mut map = PrefixMap::new();
+    /// map.insert(word("Rust"), 1);
+    ///
+    /// let (key, value) = map.get_key_value(word("rUST")).unwrap();
+    /// assert_eq!(key.iter().map(|x| x.0).collect::<Vec<_>>(), b"Rust");
+    /// assert_eq!(value, &1);
+    /// ```
+    pub fn get_key_value<Q>(&self, key: Q) -> Option<(Vec<K>, &V)>
+    where
+        Q: AsRef<[K]>,
+    {
+        let (path, n) = self.root.prefix_path(key.as_ref())?;
+        let tree = path.last()?;
+        if n != tree.key().len() {
+            return None;
+        }
+        let value = tree.value()?;
+        Some((path.iter().flat_map(|x| x.key()).cloned().collect(), value))
+    }
+
     /// Returns a mutable reference to the value corresponding to the key.
     ///
     /// # Examples
@@ -102,7 +215,8 @@ impl<K: Eq + Clone, V> PrefixMap<K, V> {
         self.root.find_mut(key.as_ref()).and_then(|x| x.value_mut())
     }
 
-    /// Inserts a key-value pair into the map.
+    /// Returns the value of the longest key in the map which is a prefix of
+    /// the given key, along with the length of that prefix.
     ///
     /// # Examples
     ///
@@ -110,44 +224,51 @@ impl<K: Eq + Clone, V> PrefixMap<K, V> {
     /// use prefix_tree::PrefixMap;
     ///
     /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
-    /// assert_eq!(map.insert("a", 42), None);
-    /// assert_eq!(map.is_empty(), false);
-    /// assert_eq!(map.insert("a", 5), Some(42));
-    /// assert_eq!(map.get("a"), Some(&5));
+    /// map.insert("/", 1);
+    /// map.insert("/foo", 2);
+    /// map.insert("/foo/bar", 3);
+    /// assert_eq!(map.longest_prefix("/foo/baz"), Some((4, &2)));
+    /// assert_eq!(map.longest_prefix("/foo/bar"), Some((8, &3)));
+    /// assert_eq!(map.longest_prefix("/qux"), Some((1, &1)));
+    /// assert_eq!(map.longest_prefix("qux"), None);
     /// ```
-    pub fn insert<Q>(&mut self, key: Q, value: V) -> Option<V>
+    pub fn longest_prefix<Q>(&self, key: Q) -> Option<(usize, &V)>
     where
         Q: AsRef<[K]>,
     {
-        let old = self.root.insert(key.as_ref(), value);
-        if old.is_none() {
-            self.length += 1;
-        }
-        old
+        self.root
+            .longest_prefix(key.as_ref())
+            .and_then(|(n, x)| x.value().map(|x| (n, x)))
     }
 
-    /// Removes a key from the map, returning the value at the key
-    /// if the key was previously in the map.
+    /// Returns the longest key stored in the map which is a prefix of the
+    /// given key, along with its value.
     ///
     /// # Examples
     ///
-    ///
     /// ```
     /// use prefix_tree::PrefixMap;
     ///
     /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
-    /// assert_eq!(map.insert("a", 42), None);
-    /// assert_eq!(map.remove("a"), Some(42));
-    /// assert_eq!(map.get("a"), None);
+    /// map.insert("/", 1);
+    /// map.insert("/foo", 2);
+    /// assert_eq!(
+    ///     map.longest_prefix_key_value("/foo/bar"),
+    ///     Some((b"/foo".to_vec(), &2))
+    /// );
+    /// assert_eq!(map.longest_prefix_key_value("foo"), None);
     /// ```
-    pub fn remove<Q>(&mut self, key: Q) -> Option<V>
+    pub fn longest_prefix_key_value<Q>(&self, key: Q) -> Option<(Vec<K>, &V)>
     where
         Q: AsRef<[K]>,
     {
-        self.root.remove(key.as_ref())
+        let key = key.as_ref();
+        let n = self.root.longest_prefix(key)?.0;
+        self.get_key_value(&key[..n])
     }
 
-    /// Returns `true` if the map contains no elements.
+    /// Returns a mutable reference to the value of the longest key in the map
+    /// which is a prefix of the given key, along with the length of that prefix.
     ///
     /// # Examples
     ///
@@ -155,15 +276,28 @@ impl<K: Eq + Clone, V> PrefixMap<K, V> {
     /// use prefix_tree::PrefixMap;
     ///
     /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
-    /// assert_eq!(map.is_empty(), true);
-    /// map.insert("foo", 1);
-    /// assert_eq!(map.is_empty(), false);
+    /// map.insert("/", 1);
+    /// map.insert("/foo", 2);
+    /// if let Some((_, x)) = map.longest_prefix_mut("/foo/bar") {
+    ///     *x = 3;
+    /// }
+    /// assert_eq!(map.get("/foo"), Some(&3));
     /// ```
-    pub fn is_empty(&self) -> bool {
-        self.len() == 0
+    pub fn longest_prefix_mut<Q>(&mut self, key: Q) -> Option<(usize, &mut V)>
+    where
+        Q: AsRef<[K]>,
+    {
+        let key = key.as_ref();
+        let n = self.root.longest_prefix(key)?.0;
+        self.root
+            .find_mut(&key[..n])
+            .and_then(|x| x.value_mut())
+            .map(|x| (n, x))
     }
 
-    /// Returns the number of elements in the map.
+    /// Gets an iterator over the values of all keys in the map which are
+    /// prefixes of the given key, along with the lengths of those prefixes,
+    /// shortest first.
     ///
     /// # Examples
     ///
@@ -171,15 +305,28 @@ impl<K: Eq + Clone, V> PrefixMap<K, V> {
     /// use prefix_tree::PrefixMap;
     ///
     /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
-    /// assert_eq!(map.len(), 0);
-    /// map.insert("foo", 1);
-    /// assert_eq!(map.len(), 1);
+    /// map.insert("a", 1);
+    /// map.insert("ab", 2);
+    /// map.insert("abc", 3);
+    /// map.insert("b", 4);
+    ///
+    /// assert_eq!(
+    ///     map.prefixes_of("abd").collect::<Vec<_>>(),
+    ///     vec![(1, &1), (2, &2)]
+    /// );
     /// ```
-    pub fn len(&self) -> usize {
-        self.length
+    pub fn prefixes_of<Q>(&self, key: Q) -> PrefixesOf<'_, K, V, Q>
+    where
+        Q: AsRef<[K]>,
+    {
+        PrefixesOf {
+            node: Some(&self.root),
+            depth: 0,
+            key,
+        }
     }
 
-    /// Gets an iterator over the entries of the map, in arbitrary order.
+    /// Returns the first entry in the map, in lexicographic order of keys.
     ///
     /// # Examples
     ///
@@ -187,181 +334,2720 @@ impl<K: Eq + Clone, V> PrefixMap<K, V> {
     /// use prefix_tree::PrefixMap;
     ///
     /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
-    /// map.insert("1", 9);
-    /// map.insert("2", 8);
-    /// map.insert("3", 7);
+    /// assert_eq!(map.first_key_value(), None);
+    /// map.insert("b", 1);
+    /// map.insert("ab", 2);
+    /// assert_eq!(map.first_key_value(), Some((b"ab".to_vec(), &2)));
+    /// ```
+    pub fn first_key_value(&self) -> Option<(Vec<K>, &V)> {
+        Range::new(&self.root, Bound::Unbounded, Bound::Unbounded).next()
+    }
+
+    /// Returns the last entry in the map, in lexicographic order of keys.
+    ///
+    /// # Examples
     ///
-    /// for (key, value) in map.iter() {
-    ///     println!("{:?}: {:?}", key, value);
-    /// }
     /// ```
-    pub fn iter(&self) -> Iter<K, V> {
-        Iter {
-            root: &self.root,
-            stack: vec![IterStackItem {
-                iter: self.root.children().iter(),
-                key_fragment: &self.root.key(),
-            }],
-            length: self.length,
-        }
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// assert_eq!(map.last_key_value(), None);
+    /// map.insert("b", 1);
+    /// map.insert("ab", 2);
+    /// assert_eq!(map.last_key_value(), Some((b"b".to_vec(), &1)));
+    /// ```
+    pub fn last_key_value(&self) -> Option<(Vec<K>, &V)> {
+        Range::new(&self.root, Bound::Unbounded, Bound::Unbounded).next_back()
     }
 
-    /// Gets an iterator over the keys of the map, in arbitrary order.
+    /// Removes the first entry in the map and returns it.
     ///
     /// # Examples
     ///
     /// ```
     /// use prefix_tree::PrefixMap;
     ///
-    /// let mut map: PrefixMap<i32, i32> = PrefixMap::new();
-    /// map.insert([1], 2);
-    /// map.insert([2], 3);
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("b", 1);
+    /// map.insert("ab", 2);
+    /// assert_eq!(map.pop_first(), Some((b"ab".to_vec(), 2)));
+    /// assert_eq!(map.pop_first(), Some((b"b".to_vec(), 1)));
+    /// assert_eq!(map.pop_first(), None);
+    /// ```
+    pub fn pop_first(&mut self) -> Option<(Vec<K>, V)> {
+        let (path, key) = self.root.end_path(false)?;
+        let value = self.root.remove_at(&path)?;
+        Some((key, value))
+    }
+
+    /// Removes the last entry in the map and returns it.
+    ///
+    /// # Examples
     ///
-    /// assert_eq!(map.keys().collect::<Vec<_>>(), vec![vec![1], vec![2]]);
     /// ```
-    pub fn keys(&self) -> Keys<K, V> {
-        Keys { inner: self.iter() }
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("b", 1);
+    /// map.insert("ab", 2);
+    /// assert_eq!(map.pop_last(), Some((b"b".to_vec(), 1)));
+    /// assert_eq!(map.pop_last(), Some((b"ab".to_vec(), 2)));
+    /// assert_eq!(map.pop_last(), None);
+    /// ```
+    pub fn pop_last(&mut self) -> Option<(Vec<K>, V)> {
+        let (path, key) = self.root.end_path(true)?;
+        let value = self.root.remove_at(&path)?;
+        Some((key, value))
     }
 
-    /// Gets an iterator over the values of the map, in arbitrary order.
+    /// Returns the entry with the greatest key strictly less than the given
+    /// key, which doesn't need to be in the map.
     ///
     /// # Examples
     ///
     /// ```
     /// use prefix_tree::PrefixMap;
     ///
-    /// let mut map: PrefixMap<i32, i32> = PrefixMap::new();
-    /// map.insert([1], 2);
-    /// map.insert([2], 3);
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("ab", 2);
+    /// map.insert("b", 3);
     ///
-    /// assert_eq!(map.values().cloned().collect::<Vec<_>>(), vec![2, 3]);
+    /// assert_eq!(map.predecessor("b"), Some((b"ab".to_vec(), &2)));
+    /// assert_eq!(map.predecessor("aa"), Some((b"a".to_vec(), &1)));
+    /// assert_eq!(map.predecessor("a"), None);
     /// ```
-    pub fn values(&self) -> Values<K, V> {
-        Values { inner: self.iter() }
+    pub fn predecessor<Q>(&self, key: Q) -> Option<(Vec<K>, &V)>
+    where
+        Q: AsRef<[K]>,
+    {
+        RawIter::before(&self.root, Bound::Excluded(key.as_ref())).next_back()
     }
-}
 
-impl<'a, K: 'a + Eq + Clone, V: 'a> FromIterator<(&'a [K], V)> for PrefixMap<K, V> {
-    fn from_iter<I>(iter: I) -> PrefixMap<K, V>
+    /// Returns the entry with the least key strictly greater than the given
+    /// key, which doesn't need to be in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("ab", 2);
+    /// map.insert("b", 3);
+    ///
+    /// assert_eq!(map.successor("a"), Some((b"ab".to_vec(), &2)));
+    /// assert_eq!(map.successor("ac"), Some((b"b".to_vec(), &3)));
+    /// assert_eq!(map.successor("b"), None);
+    /// ```
+    pub fn successor<Q>(&self, key: Q) -> Option<(Vec<K>, &V)>
     where
-        I: IntoIterator<Item = (&'a [K], V)>,
+        Q: AsRef<[K]>,
     {
-        let mut map = PrefixMap::new();
-        iter.into_iter().for_each(|(k, v)| {
-            map.insert(k, v);
-        });
-        map
+        RawIter::after(&self.root, Bound::Excluded(key.as_ref())).next()
     }
-}
-
-impl<'a, K: 'a + Eq + Clone, V: 'a> IntoIterator for &'a PrefixMap<K, V> {
-    type Item = (Vec<K>, &'a V);
-
-    type IntoIter = Iter<'a, K, V>;
 
-    fn into_iter(self) -> Self::IntoIter {
-        self.iter()
+    /// Returns the number of keys in the map which are strictly less than the
+    /// given key, which doesn't need to be in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("ab", 2);
+    /// map.insert("b", 3);
+    ///
+    /// assert_eq!(map.rank("a"), 0);
+    /// assert_eq!(map.rank("aa"), 1);
+    /// assert_eq!(map.rank("b"), 2);
+    /// assert_eq!(map.rank("c"), 3);
+    /// ```
+    pub fn rank<Q>(&self, key: Q) -> usize
+    where
+        Q: AsRef<[K]>,
+    {
+        self.root.rank(key.as_ref())
     }
-}
-
-struct IterStackItem<'a, K: 'a, V: 'a> {
-    iter: std::slice::Iter<'a, Tree<K, V>>,
-    key_fragment: &'a [K],
-}
-
-pub struct Iter<'a, K: 'a, V: 'a> {
-    root: &'a Tree<K, V>,
-    stack: Vec<IterStackItem<'a, K, V>>,
-    length: usize,
-}
 
-impl<'a, K: 'a + Eq + Clone, V: 'a> Iterator for Iter<'a, K, V> {
-    type Item = (Vec<K>, &'a V);
+    /// Returns the entry at the given position in lexicographic order of
+    /// keys, or `None` if the index is out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("b", 3);
+    /// map.insert("ab", 2);
+    /// map.insert("a", 1);
+    ///
+    /// assert_eq!(map.select(1), Some((b"ab".to_vec(), &2)));
+    /// assert_eq!(map.select(3), None);
+    /// ```
+    pub fn select(&self, index: usize) -> Option<(Vec<K>, &V)> {
+        self.root.select(index)
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.length == 1 && self.root.value().is_some() {
-            self.length = 0;
-            return self.root.value().map(|x| (vec![], x));
-        }
-        while let Some(IterStackItem { iter, .. }) = self.stack.last_mut() {
-            if let Some(tree) = iter.next() {
-                self.stack.push(IterStackItem {
-                    iter: tree.children().iter(),
-                    key_fragment: tree.key(),
-                });
-                if tree.value().is_some() {
-                    self.length -= 1;
-                    return Some((
-                        self.stack
-                            .iter()
-                            .map(|x| x.key_fragment)
-                            .flatten()
-                            .cloned()
-                            .collect(),
-                        tree.value().unwrap(),
-                    ));
-                }
-            } else {
-                self.stack.pop();
-            }
+    /// Returns a view into the part of the map whose keys start with the given
+    /// prefix, or `None` if there are no such keys.
+    ///
+    /// Keys used with and returned by the view are relative to the prefix.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("/tenant/42/users", 1);
+    /// map.insert("/tenant/42/groups", 2);
+    /// map.insert("/tenant/7/users", 3);
+    ///
+    /// let tenant = map.subtrie("/tenant/42/").unwrap();
+    /// assert_eq!(tenant.len(), 2);
+    /// assert_eq!(tenant.get("users"), Some(&1));
+    /// assert!(map.subtrie("/tenant/8/").is_none());
+    /// ```
+    pub fn subtrie<Q>(&self, prefix: Q) -> Option<SubTrie<'_, K, V>>
+    where
+        Q: AsRef<[K]>,
+    {
+        let (tree, offset) = self.root.find_prefix(prefix.as_ref())?;
+        if tree.count() == 0 {
+            return None;
         }
-        None
+        Some(SubTrie { tree, offset })
     }
 
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        (self.length, Some(self.length))
+    /// Returns a mutable view into the part of the map whose keys start with
+    /// the given prefix, or `None` if there are no such keys.
+    ///
+    /// Keys used with and returned by the view are relative to the prefix.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("/tenant/42/users", 1);
+    /// map.insert("/tenant/7/users", 3);
+    ///
+    /// if let Some(mut tenant) = map.subtrie_mut("/tenant/42/") {
+    ///     *tenant.get_mut("users").unwrap() += 10;
+    /// }
+    /// assert_eq!(map.get("/tenant/42/users"), Some(&11));
+    /// ```
+    pub fn subtrie_mut<Q>(&mut self, prefix: Q) -> Option<SubTrieMut<'_, K, V>>
+    where
+        Q: AsRef<[K]>,
+    {
+        let (tree, offset) = self.root.find_prefix_mut(prefix.as_ref())?;
+        if tree.count() == 0 {
+            return None;
+        }
+        Some(SubTrieMut { tree, offset })
     }
-}
 
-impl<'a, K: 'a + Eq + Clone, V: 'a> ExactSizeIterator for Iter<'a, K, V> {
-    fn len(&self) -> usize {
-        self.length
+    /// Returns a cursor at the root of the map, which can be moved down one
+    /// key element at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("if", 1);
+    /// map.insert("iff", 2);
+    ///
+    /// let mut cursor = map.cursor();
+    /// let mut matched = vec![];
+    /// for c in "iffy".bytes() {
+    ///     if !cursor.advance(&c) {
+    ///         break;
+    ///     }
+    ///     if let Some(value) = cursor.value() {
+    ///         matched.push(*value);
+    ///     }
+    /// }
+    /// assert_eq!(matched, vec![1, 2]);
+    /// ```
+    pub fn cursor(&self) -> Cursor<'_, K, V> {
+        Cursor {
+            root: &self.root,
+            node: &self.root,
+            offset: 0,
+        }
     }
-}
-
-impl<'a, K: 'a + Eq + Clone, V: 'a> FusedIterator for Iter<'a, K, V> {}
 
-pub struct Keys<'a, K: 'a, V: 'a> {
-    inner: Iter<'a, K, V>,
-}
-
-impl<'a, K: 'a + Eq + Clone, V: 'a> Iterator for Keys<'a, K, V> {
-    type Item = Vec<K>;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        self.inner.next().map(|(k, _)| k)
+    /// Returns a cursor at the root of the map, which can be moved down one
+    /// key element at a time and edit the map at its position.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("/home/a/x", 1);
+    /// map.insert("/home/b/y", 2);
+    ///
+    /// let mut cursor = map.cursor_mut();
+    /// for c in "/home/a/".bytes() {
+    ///     cursor.advance(&c);
+    /// }
+    /// cursor.insert("z", 3);
+    /// assert_eq!(map.len(), 3);
+    /// assert_eq!(map.get("/home/a/z"), Some(&3));
+    /// ```
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, K, V> {
+        CursorMut {
+            map: self,
+            path: vec![],
+            offset: 0,
+            key: vec![],
+        }
+    }
+
+    /// Inserts a key-value pair into the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// assert_eq!(map.insert("a", 42), None);
+    /// assert_eq!(map.is_empty(), false);
+    /// assert_eq!(map.insert("a", 5), Some(42));
+    /// assert_eq!(map.get("a"), Some(&5));
+    /// ```
+    pub fn insert<Q>(&mut self, key: Q, value: V) -> Option<V>
+    where
+        Q: AsRef<[K]>,
+    {
+        self.root.insert(key.as_ref(), value)
+    }
+
+    /// Returns the number of keys in the map which start with the given prefix.
+    ///
+    /// This doesn't visit the matching keys, which are counted as they are
+    /// inserted and removed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("apple", 1);
+    /// map.insert("apply", 2);
+    /// map.insert("banana", 3);
+    /// assert_eq!(map.count_prefix("app"), 2);
+    /// assert_eq!(map.count_prefix("b"), 1);
+    /// assert_eq!(map.count_prefix("c"), 0);
+    /// assert_eq!(map.count_prefix(""), 3);
+    /// ```
+    pub fn count_prefix<Q>(&self, prefix: Q) -> usize
+    where
+        Q: AsRef<[K]>,
+    {
+        self.root
+            .find_prefix(prefix.as_ref())
+            .map_or(0, |(x, _)| x.count())
+    }
+
+    /// Removes a key from the map, returning the value at the key
+    /// if the key was previously in the map.
+    ///
+    /// # Examples
+    ///
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// assert_eq!(map.insert("a", 42), None);
+    /// assert_eq!(map.remove("a"), Some(42));
+    /// assert_eq!(map.get("a"), None);
+    /// assert_eq!(map.len(), 0);
+    /// ```
+    pub fn remove<Q>(&mut self, key: Q) -> Option<V>
+    where
+        Q: AsRef<[K]>,
+    {
+        self.root.remove(key.as_ref())
+    }
+
+    /// Gets the given key's corresponding entry in the map for in-place manipulation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut count: PrefixMap<u8, usize> = PrefixMap::new();
+    /// for word in "a b a c a b".split(' ') {
+    ///     *count.entry(word).or_insert(0) += 1;
+    /// }
+    /// assert_eq!(count.get("a"), Some(&3));
+    /// assert_eq!(count.get("b"), Some(&2));
+    /// assert_eq!(count.get("c"), Some(&1));
+    /// ```
+    pub fn entry<Q>(&mut self, key: Q) -> Entry<'_, K, V>
+    where
+        Q: AsRef<[K]>,
+    {
+        let key = key.as_ref();
+        match self.root.locate(key) {
+            Ok(path) => Entry::Occupied(OccupiedEntry {
+                map: self,
+                key: key.to_vec(),
+                path,
+            }),
+            Err((path, offset)) => Entry::Vacant(VacantEntry {
+                map: self,
+                key: key.to_vec(),
+                path,
+                offset,
+            }),
+        }
+    }
+
+    /// Removes every key starting with the given prefix from the map,
+    /// returning them in a new map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("/tmp/a", 1);
+    /// map.insert("/tmp/b", 2);
+    /// map.insert("/usr", 3);
+    ///
+    /// let tmp = map.remove_prefix("/tmp/");
+    /// assert_eq!(tmp.len(), 2);
+    /// assert_eq!(tmp.get("/tmp/a"), Some(&1));
+    /// assert_eq!(map.len(), 1);
+    /// assert_eq!(map.get("/tmp/a"), None);
+    /// ```
+    pub fn remove_prefix<Q>(&mut self, prefix: Q) -> PrefixMap<K, V>
+    where
+        Q: AsRef<[K]>,
+    {
+        match self.root.remove_prefix_below(prefix.as_ref()) {
+            Some(subtree) => PrefixMap {
+                root: Tree::from_subtree(subtree),
+            },
+            None => PrefixMap::new(),
+        }
+    }
+
+    /// Splits the map in two at the given key, returning a new map with every
+    /// key greater than or equal to it, in lexicographic order.
+    ///
+    /// Subtrees lying entirely on one side of the key are moved as a whole.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("ab", 2);
+    /// map.insert("b", 3);
+    ///
+    /// let tail = map.split_off("aa");
+    /// assert_eq!(map.keys().collect::<Vec<_>>(), vec![b"a".to_vec()]);
+    /// assert_eq!(
+    ///     tail.keys().collect::<Vec<_>>(),
+    ///     vec![b"ab".to_vec(), b"b".to_vec()]
+    /// );
+    /// ```
+    pub fn split_off<Q>(&mut self, key: Q) -> PrefixMap<K, V>
+    where
+        Q: AsRef<[K]>,
+    {
+        PrefixMap {
+            root: self.root.split_off_below(key.as_ref()),
+        }
+    }
+
+    /// Splits the map into the entries whose keys start with the given prefix
+    /// and the rest, in this order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("/tmp/a", 1);
+    /// map.insert("/tmp/b", 2);
+    /// map.insert("/usr", 3);
+    ///
+    /// let (tmp, rest) = map.partition_prefix("/tmp/");
+    /// assert_eq!(tmp.len(), 2);
+    /// assert_eq!(rest.len(), 1);
+    /// assert_eq!(rest.get("/usr"), Some(&3));
+    /// ```
+    pub fn partition_prefix<Q>(mut self, prefix: Q) -> (PrefixMap<K, V>, PrefixMap<K, V>)
+    where
+        Q: AsRef<[K]>,
+    {
+        let inside = self.remove_prefix(prefix);
+        (inside, self)
+    }
+
+    /// Moves all entries of `other` into the map. Keys found in both maps
+    /// get the value returned by `f`, which is given the key, the value in
+    /// this map and the value in `other`.
+    ///
+    /// Parts of `other` which don't overlap with this map are moved over as
+    /// whole subtrees, without inserting their keys one by one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut a: PrefixMap<u8, i32> = PrefixMap::new();
+    /// a.insert("apple", 1);
+    /// a.insert("banana", 2);
+    /// let mut b: PrefixMap<u8, i32> = PrefixMap::new();
+    /// b.insert("apple", 10);
+    /// b.insert("cherry", 20);
+    ///
+    /// a.merge_with(b, |_, x, y| x + y);
+    /// assert_eq!(a.len(), 3);
+    /// assert_eq!(a.get("apple"), Some(&11));
+    /// assert_eq!(a.get("cherry"), Some(&20));
+    /// ```
+    pub fn merge_with<F>(&mut self, other: PrefixMap<K, V>, mut f: F)
+    where
+        F: FnMut(&[K], V, V) -> V,
+    {
+        self.root.merge(other.root, &mut vec![], &mut f)
+    }
+
+    /// Moves every key starting with `from` so that it starts with `to`
+    /// instead, and returns the number of keys moved. Keys which are already
+    /// in the map get the value returned by `f`, which is given the new key,
+    /// the value already there and the value being moved.
+    ///
+    /// The subtree holding the keys is detached and attached again at its new
+    /// place, rather than moving its keys one by one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("/home/old/a", 1);
+    /// map.insert("/home/old/b", 2);
+    /// map.insert("/home/new/b", 3);
+    ///
+    /// // Keep the files which are already at the destination.
+    /// let moved = map.rename_prefix("/home/old/", "/home/new/", |_, existing, _| existing);
+    /// assert_eq!(moved, 2);
+    /// assert_eq!(map.len(), 2);
+    /// assert_eq!(map.get("/home/new/a"), Some(&1));
+    /// assert_eq!(map.get("/home/new/b"), Some(&3));
+    /// ```
+    pub fn rename_prefix<Q, R, F>(&mut self, from: Q, to: R, mut f: F) -> usize
+    where
+        Q: AsRef<[K]>,
+        R: AsRef<[K]>,
+        F: FnMut(&[K], V, V) -> V,
+    {
+        let from = from.as_ref();
+        let subtree = match self.root.remove_prefix_below(from) {
+            Some(subtree) => subtree,
+            None => return 0,
+        };
+        let count = subtree.count();
+        if let Some(subtree) = subtree.with_key_prefix(from.len(), to.as_ref()) {
+            self.root
+                .merge(Tree::from_subtree(subtree), &mut vec![], &mut f);
+        }
+        count
+    }
+
+    /// Moves all entries of `other` into the map, leaving `other` empty.
+    /// Values in `other` replace those of equal keys in this map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut a: PrefixMap<u8, i32> = PrefixMap::new();
+    /// a.insert("apple", 1);
+    /// a.insert("banana", 2);
+    /// let mut b: PrefixMap<u8, i32> = PrefixMap::new();
+    /// b.insert("apple", 10);
+    /// b.insert("cherry", 20);
+    ///
+    /// a.append(&mut b);
+    /// assert!(b.is_empty());
+    /// assert_eq!(a.len(), 3);
+    /// assert_eq!(a.get("apple"), Some(&10));
+    /// ```
+    pub fn append(&mut self, other: &mut PrefixMap<K, V>) {
+        let other = mem::replace(other, PrefixMap::new());
+        self.merge_with(other, |_, _, b| b)
+    }
+
+    /// Returns `true` if the map contains no elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// assert_eq!(map.is_empty(), true);
+    /// map.insert("foo", 1);
+    /// assert_eq!(map.is_empty(), false);
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of elements in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// assert_eq!(map.len(), 0);
+    /// map.insert("foo", 1);
+    /// assert_eq!(map.len(), 1);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.root.count()
+    }
+
+    /// Gets an iterator over the entries of the map, in lexicographic order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("3", 7);
+    /// map.insert("1", 9);
+    /// map.insert("2", 8);
+    ///
+    /// for (key, value) in map.iter() {
+    ///     println!("{:?}: {:?}", key, value);
+    /// }
+    /// ```
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter::new(&[&self.root], 0)
+    }
+
+    /// Calls a closure on every entry of the map, in lexicographic order.
+    ///
+    /// Unlike `iter`, which allocates a new `Vec` for every key, the keys
+    /// are built in a single buffer which is only borrowed by the closure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("ab", 2);
+    /// map.insert("a", 1);
+    ///
+    /// let mut entries = vec![];
+    /// map.for_each_entry(|key, value| entries.push(format!("{:?}={}", key, value)));
+    /// assert_eq!(entries, vec!["[97]=1", "[97, 98]=2"]);
+    /// ```
+    pub fn for_each_entry<'a, F>(&'a self, mut f: F)
+    where
+        F: FnMut(&[K], &'a V),
+    {
+        self.root.for_each(&mut vec![], &mut f)
+    }
+
+    /// Gets a mutable iterator over the entries of the map, in lexicographic
+    /// order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("b", 2);
+    ///
+    /// for (key, value) in map.iter_mut() {
+    ///     if key == b"a" {
+    ///         *value += 10;
+    ///     }
+    /// }
+    /// assert_eq!(map.get("a"), Some(&11));
+    /// assert_eq!(map.get("b"), Some(&2));
+    /// ```
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut::new(vec![], Some(&mut self.root))
+    }
+
+    /// Gets an iterator over the entries of the map whose keys start with
+    /// the given prefix, in lexicographic order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("apple", 1);
+    /// map.insert("apply", 2);
+    /// map.insert("banana", 3);
+    ///
+    /// assert_eq!(
+    ///     map.iter_prefix("app").collect::<Vec<_>>(),
+    ///     vec![(b"apple".to_vec(), &1), (b"apply".to_vec(), &2)]
+    /// );
+    /// assert_eq!(map.iter_prefix("c").count(), 0);
+    /// ```
+    pub fn iter_prefix<Q>(&self, prefix: Q) -> Iter<'_, K, V>
+    where
+        Q: AsRef<[K]>,
+    {
+        match self.root.prefix_path(prefix.as_ref()) {
+            Some((path, _)) => Iter::new(&path, 0),
+            None => Iter::new(&[], 0),
+        }
+    }
+
+    /// Gets a mutable iterator over the entries of the map whose keys start
+    /// with the given prefix, in lexicographic order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("apple", 1);
+    /// map.insert("apply", 2);
+    /// map.insert("banana", 3);
+    ///
+    /// for (_, value) in map.iter_prefix_mut("app") {
+    ///     *value *= 10;
+    /// }
+    /// assert_eq!(map.values().cloned().collect::<Vec<_>>(), vec![10, 20, 3]);
+    /// ```
+    pub fn iter_prefix_mut<Q>(&mut self, prefix: Q) -> IterMut<'_, K, V>
+    where
+        Q: AsRef<[K]>,
+    {
+        let prefix = prefix.as_ref();
+        match self.root.find_prefix_mut(prefix) {
+            Some((tree, n)) => IterMut::new(prefix[..prefix.len() - n].to_vec(), Some(tree)),
+            None => IterMut::new(vec![], None),
+        }
+    }
+
+    /// Gets an iterator over the entries of the map whose keys fall within
+    /// the given range, in lexicographic order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("ab", 2);
+    /// map.insert("b", 3);
+    /// map.insert("ba", 4);
+    /// map.insert("c", 5);
+    ///
+    /// assert_eq!(
+    ///     map.range("ab".."ba").collect::<Vec<_>>(),
+    ///     vec![(b"ab".to_vec(), &2), (b"b".to_vec(), &3)]
+    /// );
+    /// assert_eq!(
+    ///     map.range("b"..).rev().map(|(_, v)| *v).collect::<Vec<_>>(),
+    ///     vec![5, 4, 3]
+    /// );
+    /// ```
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, K, V>
+    where
+        Q: AsRef<[K]>,
+        R: RangeBounds<Q>,
+    {
+        Range::new(
+            &self.root,
+            as_slice_bound(range.start_bound()),
+            as_slice_bound(range.end_bound()),
+        )
+    }
+
+    /// Gets an iterator over the keys of the map which start with the given
+    /// prefix, in lexicographic order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("apple", 1);
+    /// map.insert("apply", 2);
+    /// map.insert("banana", 3);
+    ///
+    /// assert_eq!(
+    ///     map.keys_with_prefix("ap").collect::<Vec<_>>(),
+    ///     vec![b"apple".to_vec(), b"apply".to_vec()]
+    /// );
+    /// ```
+    pub fn keys_with_prefix<Q>(&self, prefix: Q) -> Keys<'_, K, V>
+    where
+        Q: AsRef<[K]>,
+    {
+        Keys {
+            inner: self.iter_prefix(prefix),
+        }
+    }
+
+    /// Gets an iterator over the values of the map whose keys start with the
+    /// given prefix, in lexicographic order of their keys.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("apple", 1);
+    /// map.insert("apply", 2);
+    /// map.insert("banana", 3);
+    ///
+    /// assert_eq!(map.values_with_prefix("b").collect::<Vec<_>>(), vec![&3]);
+    /// ```
+    pub fn values_with_prefix<Q>(&self, prefix: Q) -> Values<'_, K, V>
+    where
+        Q: AsRef<[K]>,
+    {
+        Values {
+            inner: self.iter_prefix(prefix),
+        }
+    }
+
+    /// Gets an iterator over the keys of the map, in lexicographic order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<i32, i32> = PrefixMap::new();
+    /// map.insert([2, 1], 3);
+    /// map.insert([1], 2);
+    /// map.insert([2], 4);
+    ///
+    /// assert_eq!(
+    ///     map.keys().collect::<Vec<_>>(),
+    ///     vec![vec![1], vec![2], vec![2, 1]]
+    /// );
+    /// assert_eq!(map.keys().next_back(), Some(vec![2, 1]));
+    /// ```
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    /// Gets an iterator over the values of the map, in lexicographic order
+    /// of their keys.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<i32, i32> = PrefixMap::new();
+    /// map.insert([2], 3);
+    /// map.insert([1], 2);
+    ///
+    /// assert_eq!(map.values().cloned().collect::<Vec<_>>(), vec![2, 3]);
+    /// ```
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    /// Gets a mutable iterator over the values of the map, in lexicographic
+    /// order of their keys.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("b", 2);
+    ///
+    /// for value in map.values_mut() {
+    ///     *value *= 2;
+    /// }
+    /// assert_eq!(map.values().cloned().collect::<Vec<_>>(), vec![2, 4]);
+    /// ```
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut {
+            inner: self.iter_mut(),
+        }
+    }
+}
+
+impl<K: Ord + Clone, V: PartialEq> PrefixMap<K, V> {
+    /// Gets an iterator over the differences between this map and `other`,
+    /// in lexicographic order of keys.
+    ///
+    /// Keys only in `other` are `Added`, keys only in this map are `Removed`,
+    /// and keys whose values differ are `Changed`, with the value in this map
+    /// first. The maps are walked lazily as the iterator advances. Parts of
+    /// them which don't overlap are listed without comparing them key by key,
+    /// and subtrees shared by both, as when diffing a map with itself, are
+    /// skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::map::DiffItem;
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut old: PrefixMap<u8, i32> = PrefixMap::new();
+    /// old.insert("10.0.0.0", 1);
+    /// old.insert("10.0.1.0", 2);
+    /// let mut new = old.clone();
+    /// new.insert("10.0.1.0", 3);
+    /// new.insert("10.0.2.0", 4);
+    /// new.remove("10.0.0.0");
+    ///
+    /// assert_eq!(
+    ///     old.diff(&new).collect::<Vec<_>>(),
+    ///     vec![
+    ///         DiffItem::Removed(b"10.0.0.0".to_vec(), &1),
+    ///         DiffItem::Changed(b"10.0.1.0".to_vec(), &2, &3),
+    ///         DiffItem::Added(b"10.0.2.0".to_vec(), &4),
+    ///     ]
+    /// );
+    /// ```
+    pub fn diff<'a>(&'a self, other: &'a PrefixMap<K, V>) -> Diff<'a, K, V> {
+        Diff {
+            inner: self.root.diff(&other.root),
+        }
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> PrefixMap<K, V> {
+    pub(crate) fn combine(&self, other: &PrefixMap<K, V>, op: SetOp) -> PrefixMap<K, V> {
+        PrefixMap {
+            root: self.root.combine(&other.root, op),
+        }
+    }
+}
+
+impl<K: Ord + Clone, V> PrefixMap<K, V> {
+    pub(crate) fn combines_any(&self, other: &PrefixMap<K, V>, op: SetOp) -> bool {
+        self.root.combines_any(&other.root, op)
+    }
+}
+
+/// A view into a single entry in a map, which may either be vacant or occupied.
+///
+/// This `enum` is constructed from the [`entry`] method on [`PrefixMap`].
+///
+/// [`entry`]: struct.PrefixMap.html#method.entry
+/// [`PrefixMap`]: struct.PrefixMap.html
+pub enum Entry<'a, K: 'a, V: 'a> {
+    /// A vacant entry.
+    Vacant(VacantEntry<'a, K, V>),
+    /// An occupied entry.
+    Occupied(OccupiedEntry<'a, K, V>),
+}
+
+/// A view into a vacant entry in a `PrefixMap`. It is part of the [`Entry`] enum.
+///
+/// [`Entry`]: enum.Entry.html
+pub struct VacantEntry<'a, K: 'a, V: 'a> {
+    map: &'a mut PrefixMap<K, V>,
+    key: Vec<K>,
+    // Path to the deepest node the key reaches, and the position in the key
+    // at which that node's key starts.
+    path: Vec<usize>,
+    offset: usize,
+}
+
+/// A view into an occupied entry in a `PrefixMap`. It is part of the [`Entry`] enum.
+///
+/// [`Entry`]: enum.Entry.html
+pub struct OccupiedEntry<'a, K: 'a, V: 'a> {
+    map: &'a mut PrefixMap<K, V>,
+    key: Vec<K>,
+    path: Vec<usize>,
+}
+
+impl<'a, K: Ord + Clone, V> Entry<'a, K, V> {
+    /// Ensures a value is in the entry by inserting the default if empty,
+    /// and returns a mutable reference to the value in the entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// *map.entry("foo").or_insert(1) += 10;
+    /// assert_eq!(map["foo"], 11);
+    /// *map.entry("foo").or_insert(1) += 10;
+    /// assert_eq!(map["foo"], 21);
+    /// ```
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the result of the default
+    /// function if empty, and returns a mutable reference to the value in the entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, String> = PrefixMap::new();
+    /// map.entry("foo").or_insert_with(|| "bar".to_string());
+    /// assert_eq!(map["foo"], "bar");
+    /// ```
+    pub fn or_insert_with<F>(self, default: F) -> &'a mut V
+    where
+        F: FnOnce() -> V,
+    {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the default value if empty,
+    /// and returns a mutable reference to the value in the entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, Option<i32>> = PrefixMap::new();
+    /// map.entry("foo").or_default();
+    /// assert_eq!(map["foo"], None);
+    /// ```
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(Default::default)
+    }
+
+    /// Provides in-place mutable access to an occupied entry before any
+    /// potential inserts into the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.entry("foo").and_modify(|x| *x += 1).or_insert(42);
+    /// assert_eq!(map["foo"], 42);
+    /// map.entry("foo").and_modify(|x| *x += 1).or_insert(42);
+    /// assert_eq!(map["foo"], 43);
+    /// ```
+    pub fn and_modify<F>(self, f: F) -> Self
+    where
+        F: FnOnce(&mut V),
+    {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+        }
+    }
+
+    /// Returns a reference to this entry's key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// assert_eq!(map.entry("foo").key(), b"foo");
+    /// ```
+    pub fn key(&self) -> &[K] {
+        match *self {
+            Entry::Occupied(ref entry) => entry.key(),
+            Entry::Vacant(ref entry) => entry.key(),
+        }
+    }
+}
+
+impl<'a, K: Ord + Clone, V> VacantEntry<'a, K, V> {
+    /// Gets a reference to the key that would be used when inserting a value
+    /// through the `VacantEntry`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// assert_eq!(map.entry("foo").key(), b"foo");
+    /// ```
+    pub fn key(&self) -> &[K] {
+        &self.key
+    }
+
+    /// Take ownership of the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    /// use prefix_tree::map::Entry;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// if let Entry::Vacant(v) = map.entry("foo") {
+    ///     assert_eq!(v.into_key(), b"foo".to_vec());
+    /// }
+    /// ```
+    pub fn into_key(self) -> Vec<K> {
+        self.key
+    }
+
+    /// Sets the value of the entry with the `VacantEntry`'s key,
+    /// and returns a mutable reference to it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    /// use prefix_tree::map::Entry;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// if let Entry::Vacant(v) = map.entry("foo") {
+    ///     v.insert(37);
+    /// }
+    /// assert_eq!(map["foo"], 37);
+    /// assert_eq!(map.len(), 1);
+    /// ```
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.map
+            .root
+            .insert_vacant_at(&self.path, &self.key[self.offset..], value)
+    }
+}
+
+impl<'a, K: Ord + Clone, V> OccupiedEntry<'a, K, V> {
+    /// Gets a reference to the key in the entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("foo", 12);
+    /// assert_eq!(map.entry("foo").key(), b"foo");
+    /// ```
+    pub fn key(&self) -> &[K] {
+        &self.key
+    }
+
+    /// Gets a reference to the value in the entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    /// use prefix_tree::map::Entry;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("foo", 12);
+    /// if let Entry::Occupied(o) = map.entry("foo") {
+    ///     assert_eq!(o.get(), &12);
+    /// }
+    /// ```
+    pub fn get(&self) -> &V {
+        self.map.root.at_path(&self.path).value().unwrap()
+    }
+
+    /// Gets a mutable reference to the value in the entry.
+    ///
+    /// If you need a reference to the `OccupiedEntry` that may outlive the
+    /// destruction of the `Entry` value, see [`into_mut`].
+    ///
+    /// [`into_mut`]: #method.into_mut
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    /// use prefix_tree::map::Entry;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("foo", 12);
+    /// if let Entry::Occupied(mut o) = map.entry("foo") {
+    ///     *o.get_mut() += 10;
+    ///     assert_eq!(*o.get(), 22);
+    /// }
+    /// assert_eq!(map["foo"], 22);
+    /// ```
+    pub fn get_mut(&mut self) -> &mut V {
+        self.map.root.at_path_mut(&self.path).value_mut().unwrap()
+    }
+
+    /// Converts the entry into a mutable reference to its value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    /// use prefix_tree::map::Entry;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("foo", 12);
+    /// if let Entry::Occupied(o) = map.entry("foo") {
+    ///     *o.into_mut() += 10;
+    /// }
+    /// assert_eq!(map["foo"], 22);
+    /// ```
+    pub fn into_mut(self) -> &'a mut V {
+        self.map.root.at_path_mut(&self.path).value_mut().unwrap()
+    }
+
+    /// Sets the value of the entry with the `OccupiedEntry`'s key,
+    /// and returns the entry's old value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    /// use prefix_tree::map::Entry;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("foo", 12);
+    /// if let Entry::Occupied(mut o) = map.entry("foo") {
+    ///     assert_eq!(o.insert(15), 12);
+    /// }
+    /// assert_eq!(map["foo"], 15);
+    /// ```
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    /// Takes the value of the entry out of the map, and returns it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    /// use prefix_tree::map::Entry;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("foo", 12);
+    /// if let Entry::Occupied(o) = map.entry("foo") {
+    ///     assert_eq!(o.remove(), 12);
+    /// }
+    /// assert_eq!(map.contains_key("foo"), false);
+    /// assert_eq!(map.len(), 0);
+    /// ```
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Takes ownership of the key and value from the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    /// use prefix_tree::map::Entry;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("foo", 12);
+    /// if let Entry::Occupied(o) = map.entry("foo") {
+    ///     assert_eq!(o.remove_entry(), (b"foo".to_vec(), 12));
+    /// }
+    /// assert_eq!(map.contains_key("foo"), false);
+    /// ```
+    pub fn remove_entry(self) -> (Vec<K>, V) {
+        let value = self.map.root.remove_at(&self.path).unwrap();
+        (self.key, value)
+    }
+}
+
+/// A view into the part of a `PrefixMap` whose keys start with a given prefix.
+///
+/// Keys are relative to the prefix. This `struct` is created by the
+/// [`subtrie`] method on [`PrefixMap`].
+///
+/// [`subtrie`]: struct.PrefixMap.html#method.subtrie
+/// [`PrefixMap`]: struct.PrefixMap.html
+pub struct SubTrie<'a, K: 'a, V: 'a> {
+    tree: &'a Tree<K, V>,
+    offset: usize,
+}
+
+/// A mutable view into the part of a `PrefixMap` whose keys start with a given prefix.
+///
+/// Keys are relative to the prefix. This `struct` is created by the
+/// [`subtrie_mut`] method on [`PrefixMap`].
+///
+/// [`subtrie_mut`]: struct.PrefixMap.html#method.subtrie_mut
+/// [`PrefixMap`]: struct.PrefixMap.html
+pub struct SubTrieMut<'a, K: 'a, V: 'a> {
+    tree: &'a mut Tree<K, V>,
+    offset: usize,
+}
+
+impl<'a, K: Ord + Clone, V> SubTrie<'a, K, V> {
+    /// Returns `true` if the view contains a value for the specified key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("foobar", 1);
+    /// let sub = map.subtrie("foo").unwrap();
+    /// assert_eq!(sub.contains_key("bar"), true);
+    /// assert_eq!(sub.contains_key("foobar"), false);
+    /// ```
+    pub fn contains_key<Q>(&self, key: Q) -> bool
+    where
+        Q: AsRef<[K]>,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("foo", 1);
+    /// map.insert("foobar", 2);
+    /// let sub = map.subtrie("fo").unwrap();
+    /// assert_eq!(sub.get("o"), Some(&1));
+    /// assert_eq!(sub.get("obar"), Some(&2));
+    /// assert_eq!(sub.get("ob"), None);
+    /// ```
+    pub fn get<Q>(&self, key: Q) -> Option<&'a V>
+    where
+        Q: AsRef<[K]>,
+    {
+        let key = key.as_ref();
+        let rest = &self.tree.key()[self.offset..];
+        if !key.starts_with(rest) {
+            return None;
+        }
+        self.tree
+            .find_below(&key[rest.len()..])
+            .and_then(|x| x.value())
+    }
+
+    /// Returns `true` if the view contains no elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("foo", 1);
+    /// assert_eq!(map.subtrie("f").unwrap().is_empty(), false);
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of elements in the view.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("foo", 1);
+    /// map.insert("foobar", 2);
+    /// map.insert("bar", 3);
+    /// assert_eq!(map.subtrie("foo").unwrap().len(), 2);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.tree.count()
+    }
+
+    /// Gets an iterator over the entries of the view, in lexicographic order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("foo", 1);
+    /// map.insert("foobar", 2);
+    /// map.insert("bar", 3);
+    ///
+    /// assert_eq!(
+    ///     map.subtrie("fo").unwrap().iter().collect::<Vec<_>>(),
+    ///     vec![(b"o".to_vec(), &1), (b"obar".to_vec(), &2)]
+    /// );
+    /// ```
+    pub fn iter(&self) -> Iter<'a, K, V> {
+        Iter::new(&[self.tree], self.offset)
+    }
+
+    /// Gets an iterator over the entries of the view whose keys start with
+    /// the given prefix, in lexicographic order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("foo", 1);
+    /// map.insert("foobar", 2);
+    /// map.insert("foobaz", 3);
+    ///
+    /// assert_eq!(
+    ///     map.subtrie("fo").unwrap().iter_prefix("obaz").collect::<Vec<_>>(),
+    ///     vec![(b"obaz".to_vec(), &3)]
+    /// );
+    /// ```
+    pub fn iter_prefix<Q>(&self, prefix: Q) -> Iter<'a, K, V>
+    where
+        Q: AsRef<[K]>,
+    {
+        let prefix = prefix.as_ref();
+        let rest = &self.tree.key()[self.offset..];
+        if rest.starts_with(prefix) {
+            self.iter()
+        } else if prefix.starts_with(rest) {
+            match self.tree.prefix_path_below(&prefix[rest.len()..]) {
+                Some((path, _)) => Iter::new(&path, self.offset),
+                None => Iter::new(&[], 0),
+            }
+        } else {
+            Iter::new(&[], 0)
+        }
+    }
+}
+
+impl<'a, K: Ord + Clone, V> SubTrieMut<'a, K, V> {
+    fn view(&self) -> SubTrie<'_, K, V> {
+        SubTrie {
+            tree: self.tree,
+            offset: self.offset,
+        }
+    }
+
+    /// Returns `true` if the view contains a value for the specified key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("foobar", 1);
+    /// let sub = map.subtrie_mut("foo").unwrap();
+    /// assert_eq!(sub.contains_key("bar"), true);
+    /// assert_eq!(sub.contains_key("foobar"), false);
+    /// ```
+    pub fn contains_key<Q>(&self, key: Q) -> bool
+    where
+        Q: AsRef<[K]>,
+    {
+        self.view().contains_key(key)
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("foobar", 1);
+    /// let sub = map.subtrie_mut("foo").unwrap();
+    /// assert_eq!(sub.get("bar"), Some(&1));
+    /// ```
+    pub fn get<Q>(&self, key: Q) -> Option<&V>
+    where
+        Q: AsRef<[K]>,
+    {
+        self.view().get(key)
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("foobar", 1);
+    /// if let Some(x) = map.subtrie_mut("foo").unwrap().get_mut("bar") {
+    ///     *x = 2;
+    /// }
+    /// assert_eq!(map.get("foobar"), Some(&2));
+    /// ```
+    pub fn get_mut<Q>(&mut self, key: Q) -> Option<&mut V>
+    where
+        Q: AsRef<[K]>,
+    {
+        let key = key.as_ref();
+        let rest = &self.tree.key()[self.offset..];
+        if !key.starts_with(rest) {
+            return None;
+        }
+        let p = rest.len();
+        self.tree
+            .find_below_mut(&key[p..])
+            .and_then(|x| x.value_mut())
+    }
+
+    /// Returns `true` if the view contains no elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("foo", 1);
+    /// assert_eq!(map.subtrie_mut("f").unwrap().is_empty(), false);
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of elements in the view.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("foo", 1);
+    /// map.insert("foobar", 2);
+    /// map.insert("bar", 3);
+    /// assert_eq!(map.subtrie_mut("foo").unwrap().len(), 2);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.view().len()
+    }
+
+    /// Gets an iterator over the entries of the view, in lexicographic order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("foo", 1);
+    /// map.insert("foobar", 2);
+    ///
+    /// assert_eq!(
+    ///     map.subtrie_mut("foo").unwrap().iter().collect::<Vec<_>>(),
+    ///     vec![(vec![], &1), (b"bar".to_vec(), &2)]
+    /// );
+    /// ```
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        self.view().iter()
+    }
+
+    /// Gets an iterator over the entries of the view whose keys start with
+    /// the given prefix, in lexicographic order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("foo", 1);
+    /// map.insert("foobar", 2);
+    ///
+    /// assert_eq!(
+    ///     map.subtrie_mut("foo").unwrap().iter_prefix("b").collect::<Vec<_>>(),
+    ///     vec![(b"bar".to_vec(), &2)]
+    /// );
+    /// ```
+    pub fn iter_prefix<Q>(&self, prefix: Q) -> Iter<'_, K, V>
+    where
+        Q: AsRef<[K]>,
+    {
+        self.view().iter_prefix(prefix)
+    }
+}
+
+/// A cursor which walks down a `PrefixMap` one key element at a time.
+///
+/// The cursor starts at the root, where the key walked so far is empty. This
+/// `struct` is created by the [`cursor`] method on [`PrefixMap`].
+///
+/// [`cursor`]: struct.PrefixMap.html#method.cursor
+/// [`PrefixMap`]: struct.PrefixMap.html
+pub struct Cursor<'a, K: 'a, V: 'a> {
+    root: &'a Tree<K, V>,
+    node: &'a Tree<K, V>,
+    offset: usize,
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> Cursor<'a, K, V> {
+    /// Moves the cursor one element further down the map, returning `false`
+    /// and leaving the cursor where it was if no key continues with it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("abc", 1);
+    ///
+    /// let mut cursor = map.cursor();
+    /// assert!(cursor.advance(&b'a'));
+    /// assert!(!cursor.advance(&b'c'));
+    /// assert!(cursor.advance(&b'b'));
+    /// ```
+    pub fn advance(&mut self, k: &K) -> bool {
+        if self.offset < self.node.key().len() {
+            if self.node.key()[self.offset] != *k {
+                return false;
+            }
+            self.offset += 1;
+        } else {
+            match self.node.child(k) {
+                Some(child) => {
+                    self.node = child;
+                    self.offset = 1;
+                }
+                None => return false,
+            }
+        }
+        true
+    }
+
+    /// Returns `true` if the cursor could advance with the given element.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("ab", 1);
+    ///
+    /// let mut cursor = map.cursor();
+    /// assert!(cursor.can_extend(&b'a'));
+    /// assert!(!cursor.can_extend(&b'b'));
+    /// ```
+    pub fn can_extend(&self, k: &K) -> bool {
+        match self.node.key().get(self.offset) {
+            Some(x) => x == k,
+            None => self.node.child(k).is_some(),
+        }
+    }
+
+    /// Returns the value of the key walked so far, if it is in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("abc", 2);
+    ///
+    /// let mut cursor = map.cursor();
+    /// cursor.advance(&b'a');
+    /// assert_eq!(cursor.value(), Some(&1));
+    /// cursor.advance(&b'b');
+    /// assert_eq!(cursor.value(), None);
+    /// cursor.advance(&b'c');
+    /// assert_eq!(cursor.value(), Some(&2));
+    /// ```
+    pub fn value(&self) -> Option<&'a V> {
+        if self.offset == self.node.key().len() {
+            self.node.value()
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if the map holds keys longer than the key walked so far
+    /// which start with it, so that the cursor can advance further.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("ab", 1);
+    ///
+    /// let mut cursor = map.cursor();
+    /// cursor.advance(&b'a');
+    /// assert!(cursor.has_children());
+    /// cursor.advance(&b'b');
+    /// assert!(!cursor.has_children());
+    /// ```
+    pub fn has_children(&self) -> bool {
+        self.offset < self.node.key().len() || !self.node.children().is_empty()
+    }
+
+    /// Moves the cursor back to the root.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("ab", 1);
+    ///
+    /// let mut cursor = map.cursor();
+    /// cursor.advance(&b'a');
+    /// cursor.reset();
+    /// assert!(!cursor.advance(&b'b'));
+    /// assert!(cursor.advance(&b'a'));
+    /// ```
+    pub fn reset(&mut self) {
+        self.node = self.root;
+        self.offset = 0;
+    }
+}
+
+/// A cursor which walks down a `PrefixMap` one key element at a time and
+/// can edit the map at its position.
+///
+/// This `struct` is created by the [`cursor_mut`] method on [`PrefixMap`].
+///
+/// [`cursor_mut`]: struct.PrefixMap.html#method.cursor_mut
+/// [`PrefixMap`]: struct.PrefixMap.html
+pub struct CursorMut<'a, K: 'a, V: 'a> {
+    map: &'a mut PrefixMap<K, V>,
+    /// Indices of the children leading to the node the cursor is in.
+    path: Vec<usize>,
+    /// Number of elements of the node's key walked so far.
+    offset: usize,
+    key: Vec<K>,
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> CursorMut<'a, K, V> {
+    fn view(&self) -> Cursor<'_, K, V> {
+        Cursor {
+            root: &self.map.root,
+            node: self.map.root.at_path(&self.path),
+            offset: self.offset,
+        }
+    }
+
+    /// Returns the key walked so far.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("ab", 1);
+    ///
+    /// let mut cursor = map.cursor_mut();
+    /// cursor.advance(&b'a');
+    /// assert_eq!(cursor.key(), b"a");
+    /// ```
+    pub fn key(&self) -> &[K] {
+        &self.key
+    }
+
+    /// Moves the cursor one element further down the map, returning `false`
+    /// and leaving the cursor where it was if no key continues with it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("ab", 1);
+    ///
+    /// let mut cursor = map.cursor_mut();
+    /// assert!(cursor.advance(&b'a'));
+    /// assert!(!cursor.advance(&b'a'));
+    /// assert!(cursor.advance(&b'b'));
+    /// ```
+    pub fn advance(&mut self, k: &K) -> bool {
+        let node = self.map.root.at_path(&self.path);
+        if self.offset < node.key().len() {
+            if node.key()[self.offset] != *k {
+                return false;
+            }
+            self.offset += 1;
+        } else {
+            match node.child_index(k) {
+                Ok(i) => {
+                    self.path.push(i);
+                    self.offset = 1;
+                }
+                Err(_) => return false,
+            }
+        }
+        self.key.push(k.clone());
+        true
+    }
+
+    /// Returns `true` if the cursor could advance with the given element.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("ab", 1);
+    ///
+    /// let cursor = map.cursor_mut();
+    /// assert!(cursor.can_extend(&b'a'));
+    /// assert!(!cursor.can_extend(&b'b'));
+    /// ```
+    pub fn can_extend(&self, k: &K) -> bool {
+        self.view().can_extend(k)
+    }
+
+    /// Returns the value of the key walked so far, if it is in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("a", 1);
+    ///
+    /// let mut cursor = map.cursor_mut();
+    /// assert_eq!(cursor.value(), None);
+    /// cursor.advance(&b'a');
+    /// assert_eq!(cursor.value(), Some(&1));
+    /// ```
+    pub fn value(&self) -> Option<&V> {
+        self.view().value()
+    }
+
+    /// Returns a mutable reference to the value of the key walked so far, if
+    /// it is in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("a", 1);
+    ///
+    /// let mut cursor = map.cursor_mut();
+    /// cursor.advance(&b'a');
+    /// *cursor.value_mut().unwrap() += 1;
+    /// assert_eq!(map.get("a"), Some(&2));
+    /// ```
+    pub fn value_mut(&mut self) -> Option<&mut V> {
+        let node = self.map.root.at_path_mut(&self.path);
+        if self.offset == node.key().len() {
+            node.value_mut()
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if the map holds keys longer than the key walked so far
+    /// which start with it, so that the cursor can advance further.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("a", 1);
+    ///
+    /// let mut cursor = map.cursor_mut();
+    /// assert!(cursor.has_children());
+    /// cursor.advance(&b'a');
+    /// assert!(!cursor.has_children());
+    /// ```
+    pub fn has_children(&self) -> bool {
+        self.view().has_children()
+    }
+
+    /// Moves the cursor back to the root.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("a", 1);
+    ///
+    /// let mut cursor = map.cursor_mut();
+    /// cursor.advance(&b'a');
+    /// cursor.reset();
+    /// assert!(cursor.key().is_empty());
+    /// ```
+    pub fn reset(&mut self) {
+        self.path.clear();
+        self.offset = 0;
+        self.key.clear();
+    }
+
+    /// Sets the value of the key walked so far, returning the old one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("abc", 1);
+    ///
+    /// let mut cursor = map.cursor_mut();
+    /// cursor.advance(&b'a');
+    /// assert_eq!(cursor.set_value(2), None);
+    /// assert_eq!(cursor.set_value(3), Some(2));
+    /// assert_eq!(map.get("a"), Some(&3));
+    /// assert_eq!(map.len(), 2);
+    /// ```
+    pub fn set_value(&mut self, value: V) -> Option<V> {
+        self.insert(&[], value)
+    }
+
+    /// Removes the value of the key walked so far and returns it.
+    ///
+    /// If no key passes through the position of the cursor anymore, the
+    /// cursor moves back to the longest prefix of its key which does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("abc", 2);
+    ///
+    /// let mut cursor = map.cursor_mut();
+    /// cursor.advance(&b'a');
+    /// cursor.advance(&b'b');
+    /// cursor.advance(&b'c');
+    /// assert_eq!(cursor.take_value(), Some(2));
+    /// assert_eq!(cursor.key(), b"a");
+    /// assert_eq!(map.len(), 1);
+    /// ```
+    pub fn take_value(&mut self) -> Option<V> {
+        self.value()?;
+        let value = self.map.root.remove_at(&self.path);
+        self.seek();
+        value
+    }
+
+    /// Inserts a value for the key walked so far followed by `suffix`,
+    /// returning the old value. The cursor stays where it is.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("/usr/", 1);
+    ///
+    /// let mut cursor = map.cursor_mut();
+    /// for c in "/usr/".bytes() {
+    ///     cursor.advance(&c);
+    /// }
+    /// cursor.insert("bin", 2);
+    /// cursor.insert("lib", 3);
+    /// assert_eq!(map.get("/usr/bin"), Some(&2));
+    /// assert_eq!(map.get("/usr/lib"), Some(&3));
+    /// ```
+    pub fn insert<Q>(&mut self, suffix: Q, value: V) -> Option<V>
+    where
+        Q: AsRef<[K]>,
+    {
+        // The node is split at most at the position of the cursor, so the
+        // path to it stays the same.
+        let node = self.map.root.at_path(&self.path);
+        let mut key = node.key()[..self.offset].to_vec();
+        key.extend_from_slice(suffix.as_ref());
+        self.map.root.insert_at(&self.path, &key, value)
+    }
+
+    /// Removes every key starting with the key walked so far, and returns
+    /// them as a new map. The cursor then moves back to the longest prefix of
+    /// its key which is still part of some key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixMap;
+    ///
+    /// let mut map: PrefixMap<u8, i32> = PrefixMap::new();
+    /// map.insert("/etc", 1);
+    /// map.insert("/usr/bin", 2);
+    /// map.insert("/usr/lib", 3);
+    ///
+    /// let mut cursor = map.cursor_mut();
+    /// for c in "/usr/".bytes() {
+    ///     cursor.advance(&c);
+    /// }
+    /// let removed = cursor.remove_subtree();
+    /// assert_eq!(cursor.key(), b"/");
+    /// assert_eq!(removed.len(), 2);
+    /// assert_eq!(removed.get("/usr/lib"), Some(&3));
+    /// assert_eq!(map.len(), 1);
+    /// ```
+    pub fn remove_subtree(&mut self) -> PrefixMap<K, V> {
+        let subtree = self.map.root.remove_subtree_at(&self.path);
+        self.seek();
+        PrefixMap {
+            root: Tree::from_subtree(subtree),
+        }
+    }
+
+    /// Walks the key of the cursor again from the root, as far as the map
+    /// still allows, after the nodes on its way have changed.
+    fn seek(&mut self) {
+        let key = mem::take(&mut self.key);
+        self.reset();
+        for k in &key {
+            if !self.advance(k) {
+                break;
+            }
+        }
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> FromIterator<(&'a [K], V)> for PrefixMap<K, V> {
+    fn from_iter<I>(iter: I) -> PrefixMap<K, V>
+    where
+        I: IntoIterator<Item = (&'a [K], V)>,
+    {
+        let mut map = PrefixMap::new();
+        iter.into_iter().for_each(|(k, v)| {
+            map.insert(k, v);
+        });
+        map
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> IntoIterator for &'a PrefixMap<K, V> {
+    type Item = (Vec<K>, &'a V);
+
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Ord + Clone, V> IntoIterator for PrefixMap<K, V> {
+    type Item = (Vec<K>, V);
+
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter::new(self.root)
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> IntoIterator for &'a mut PrefixMap<K, V> {
+    type Item = (Vec<K>, &'a mut V);
+
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+struct IterStackItem<'a, K: 'a, V: 'a> {
+    iter: slice::Iter<'a, Tree<K, V>>,
+    key_fragment: &'a [K],
+    /// The value of the node whose children are in `iter`. Walking from the
+    /// back, it is yielded once all of the children have been visited.
+    value: Option<&'a V>,
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> IterStackItem<'a, K, V> {
+    fn new(tree: &'a Tree<K, V>, children: &'a [Tree<K, V>]) -> IterStackItem<'a, K, V> {
+        IterStackItem {
+            iter: children.iter(),
+            key_fragment: tree.key(),
+            value: tree.value(),
+        }
+    }
+
+    /// Number of values not visited yet when walking in the direction of
+    /// `iter`, including `value` if `back` is set.
+    fn remaining(&self, back: bool) -> usize {
+        let below: usize = self.iter.as_slice().iter().map(|x| x.count()).sum();
+        below + (back && self.value.is_some()) as usize
+    }
+}
+
+impl<'a, K: 'a, V: 'a> Clone for IterStackItem<'a, K, V> {
+    fn clone(&self) -> Self {
+        IterStackItem {
+            iter: self.iter.clone(),
+            key_fragment: self.key_fragment,
+            value: self.value,
+        }
+    }
+}
+
+/// Walks a subtree in lexicographic order from both ends, which meet once
+/// `length` values have been yielded.
+struct RawIter<'a, K: 'a, V: 'a> {
+    front: Vec<IterStackItem<'a, K, V>>,
+    back: Vec<IterStackItem<'a, K, V>>,
+    skip: usize,
+    length: usize,
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> RawIter<'a, K, V> {
+    /// Creates an iterator over the subtree of the last node in `path`.
+    /// Keys of the preceding nodes are used as the prefix of the yielded keys,
+    /// without their first `skip` elements.
+    fn new(path: &[&'a Tree<K, V>], skip: usize) -> RawIter<'a, K, V> {
+        let mut stack = vec![];
+        let mut length = 0;
+        if let Some((tree, ancestors)) = path.split_last() {
+            stack.extend(ancestors.iter().map(|x| IterStackItem {
+                iter: [].iter(),
+                key_fragment: x.key(),
+                value: None,
+            }));
+            stack.push(IterStackItem {
+                iter: slice::from_ref(*tree).iter(),
+                key_fragment: &[],
+                value: None,
+            });
+            length = tree.count();
+        }
+        RawIter {
+            front: stack.clone(),
+            back: stack,
+            skip,
+            length,
+        }
+    }
+
+    /// Creates an iterator over the keys of the tree between the bounds.
+    fn range(root: &'a Tree<K, V>, start: Bound<&[K]>, end: Bound<&[K]>) -> RawIter<'a, K, V> {
+        let mut iter = RawIter::new(&[root], 0);
+        iter.seek_front(start);
+        iter.seek_back(end);
+        // Every key is either at or after the front, or at or before the
+        // back, so the overlap of both is what is left to iterate over.
+        let front: usize = iter.front.iter().map(|x| x.remaining(false)).sum();
+        let back: usize = iter.back.iter().map(|x| x.remaining(true)).sum();
+        iter.length = (front + back).saturating_sub(root.count());
+        iter
+    }
+
+    /// Creates an iterator over the keys of the tree after `start`, seeking
+    /// only the front. It is only meant to be advanced from the front.
+    fn after(root: &'a Tree<K, V>, start: Bound<&[K]>) -> RawIter<'a, K, V> {
+        let mut iter = RawIter::new(&[root], 0);
+        iter.seek_front(start);
+        iter
+    }
+
+    /// Creates an iterator over the keys of the tree before `end`, seeking
+    /// only the back. It is only meant to be advanced from the back.
+    fn before(root: &'a Tree<K, V>, end: Bound<&[K]>) -> RawIter<'a, K, V> {
+        let mut iter = RawIter::new(&[root], 0);
+        iter.seek_back(end);
+        iter
+    }
+
+    /// Moves the front of the iterator to the first key after `bound`.
+    fn seek_front(&mut self, bound: Bound<&[K]>) {
+        let (key, inclusive) = match bound {
+            Bound::Included(key) => (key, true),
+            Bound::Excluded(key) => (key, false),
+            Bound::Unbounded => return,
+        };
+        let mut depth = 0;
+        while let Some(top) = self.front.last_mut() {
+            let tree = match top.iter.as_slice().first() {
+                Some(tree) => tree,
+                None => return,
+            };
+            let rest = &key[depth..];
+            let n = common_prefix(tree.key(), rest);
+            if n < tree.key().len() && n < rest.len() {
+                // The keys of the subtree are either all before or all after
+                // the bound, and the following siblings are after it.
+                if tree.key()[n] < rest[n] {
+                    top.iter.next();
+                }
+                return;
+            }
+            if n == rest.len() {
+                // The key of the node starts with the bound, so only its own
+                // value can be excluded.
+                if n == tree.key().len() && !inclusive {
+                    top.iter.next();
+                    self.front.push(IterStackItem::new(tree, tree.children()));
+                }
+                return;
+            }
+            // The key of the node is a proper prefix of the bound.
+            top.iter.next();
+            depth += n;
+            let next = &key[depth];
+            let i = tree.children().partition_point(|x| x.key()[0] < *next);
+            self.front
+                .push(IterStackItem::new(tree, &tree.children()[i..]));
+        }
+    }
+
+    /// Moves the back of the iterator to the last key before `bound`.
+    fn seek_back(&mut self, bound: Bound<&[K]>) {
+        let (key, inclusive) = match bound {
+            Bound::Included(key) => (key, true),
+            Bound::Excluded(key) => (key, false),
+            Bound::Unbounded => return,
+        };
+        let mut depth = 0;
+        while let Some(top) = self.back.last_mut() {
+            let tree = match top.iter.as_slice().last() {
+                Some(tree) => tree,
+                None => return,
+            };
+            let rest = &key[depth..];
+            let n = common_prefix(tree.key(), rest);
+            if n < tree.key().len() && n < rest.len() {
+                // The keys of the subtree are either all before or all after
+                // the bound, and the preceding siblings are before it.
+                if tree.key()[n] > rest[n] {
+                    top.iter.next_back();
+                }
+                return;
+            }
+            if n == rest.len() {
+                // The key of the node starts with the bound, so only its own
+                // value can be included.
+                top.iter.next_back();
+                if n == tree.key().len() && inclusive {
+                    self.back.push(IterStackItem::new(tree, &[]));
+                }
+                return;
+            }
+            // The key of the node is a proper prefix of the bound.
+            top.iter.next_back();
+            depth += n;
+            let next = &key[depth];
+            let i = tree.children().partition_point(|x| x.key()[0] <= *next);
+            self.back
+                .push(IterStackItem::new(tree, &tree.children()[..i]));
+        }
+    }
+
+    fn key_of(&self, stack: &[IterStackItem<'a, K, V>]) -> Vec<K> {
+        stack
+            .iter()
+            .flat_map(|x| x.key_fragment)
+            .skip(self.skip)
+            .cloned()
+            .collect()
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> Iterator for RawIter<'a, K, V> {
+    type Item = (Vec<K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.length == 0 {
+            return None;
+        }
+        while let Some(IterStackItem { iter, .. }) = self.front.last_mut() {
+            if let Some(tree) = iter.next() {
+                self.front.push(IterStackItem::new(tree, tree.children()));
+                if let Some(value) = tree.value() {
+                    self.length -= 1;
+                    return Some((self.key_of(&self.front), value));
+                }
+            } else {
+                self.front.pop();
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.length, Some(self.length))
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> DoubleEndedIterator for RawIter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.length == 0 {
+            return None;
+        }
+        while let Some(IterStackItem { iter, .. }) = self.back.last_mut() {
+            if let Some(tree) = iter.next_back() {
+                self.back.push(IterStackItem::new(tree, tree.children()));
+            } else {
+                let key = self.key_of(&self.back);
+                if let Some(value) = self.back.pop().and_then(|x| x.value) {
+                    self.length -= 1;
+                    return Some((key, value));
+                }
+            }
+        }
+        None
+    }
+}
+
+pub struct Iter<'a, K: 'a, V: 'a> {
+    inner: RawIter<'a, K, V>,
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> Iter<'a, K, V> {
+    /// Creates an iterator over the subtree of the last node in `path`.
+    /// Keys of the preceding nodes are used as the prefix of the yielded keys,
+    /// without their first `skip` elements.
+    fn new(path: &[&'a Tree<K, V>], skip: usize) -> Iter<'a, K, V> {
+        Iter {
+            inner: RawIter::new(path, skip),
+        }
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> Iterator for Iter<'a, K, V> {
+    type Item = (Vec<K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> DoubleEndedIterator for Iter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> ExactSizeIterator for Iter<'a, K, V> {
+    fn len(&self) -> usize {
+        self.inner.length
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> FusedIterator for Iter<'a, K, V> {}
+
+fn as_slice_bound<K, Q: AsRef<[K]>>(bound: Bound<&Q>) -> Bound<&[K]> {
+    match bound {
+        Bound::Included(x) => Bound::Included(x.as_ref()),
+        Bound::Excluded(x) => Bound::Excluded(x.as_ref()),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+pub struct Range<'a, K: 'a, V: 'a> {
+    inner: RawIter<'a, K, V>,
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> Range<'a, K, V> {
+    fn new(root: &'a Tree<K, V>, start: Bound<&[K]>, end: Bound<&[K]>) -> Range<'a, K, V> {
+        Range {
+            inner: RawIter::range(root, start, end),
+        }
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> Iterator for Range<'a, K, V> {
+    type Item = (Vec<K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> DoubleEndedIterator for Range<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> ExactSizeIterator for Range<'a, K, V> {
+    fn len(&self) -> usize {
+        self.inner.length
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> FusedIterator for Range<'a, K, V> {}
+
+pub struct PrefixesOf<'a, K: 'a, V: 'a, Q> {
+    node: Option<&'a Tree<K, V>>,
+    depth: usize,
+    key: Q,
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a, Q: AsRef<[K]>> Iterator for PrefixesOf<'a, K, V, Q> {
+    type Item = (usize, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(tree) = self.node.take() {
+            let key = self.key.as_ref();
+            if !key[self.depth..].starts_with(tree.key()) {
+                break;
+            }
+            self.depth += tree.key().len();
+            self.node = key.get(self.depth).and_then(|x| tree.child(x));
+            if let Some(value) = tree.value() {
+                return Some((self.depth, value));
+            }
+        }
+        None
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a, Q: AsRef<[K]>> FusedIterator for PrefixesOf<'a, K, V, Q> {}
+
+pub struct Keys<'a, K: 'a, V: 'a> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> Iterator for Keys<'a, K, V> {
+    type Item = Vec<K>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
     }
 }
 
-impl<'a, K: 'a + Eq + Clone, V: 'a> ExactSizeIterator for Keys<'a, K, V> {
+impl<'a, K: 'a + Ord + Clone, V: 'a> DoubleEndedIterator for Keys<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(k, _)| k)
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> ExactSizeIterator for Keys<'a, K, V> {
     fn len(&self) -> usize {
-        self.inner.length
+        self.inner.len()
     }
 }
 
-impl<'a, K: 'a + Eq + Clone, V: 'a> FusedIterator for Keys<'a, K, V> {}
+impl<'a, K: 'a + Ord + Clone, V: 'a> FusedIterator for Keys<'a, K, V> {}
 
 pub struct Values<'a, K: 'a, V: 'a> {
     inner: Iter<'a, K, V>,
 }
 
-impl<'a, K: 'a + Eq + Clone, V: 'a> Iterator for Values<'a, K, V> {
+impl<'a, K: 'a + Ord + Clone, V: 'a> Iterator for Values<'a, K, V> {
     type Item = &'a V;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.inner.next().map(|(_, v)| v)
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> DoubleEndedIterator for Values<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(_, v)| v)
+    }
 }
 
-impl<'a, K: 'a + Eq + Clone, V: 'a> ExactSizeIterator for Values<'a, K, V> {
+impl<'a, K: 'a + Ord + Clone, V: 'a> ExactSizeIterator for Values<'a, K, V> {
     fn len(&self) -> usize {
-        self.inner.length
+        self.inner.len()
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> FusedIterator for Values<'a, K, V> {}
+
+struct IterMutStackItem<'a, K: 'a, V: 'a> {
+    iter: slice::IterMut<'a, Tree<K, V>>,
+    key_fragment: &'a [K],
+}
+
+pub struct IterMut<'a, K: 'a, V: 'a> {
+    prefix: Vec<K>,
+    stack: Vec<IterMutStackItem<'a, K, V>>,
+    length: usize,
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> IterMut<'a, K, V> {
+    /// Creates an iterator over the subtree of `tree`, whose ancestors have
+    /// the keys making up `prefix`.
+    fn new(prefix: Vec<K>, tree: Option<&'a mut Tree<K, V>>) -> IterMut<'a, K, V> {
+        let length = tree.as_ref().map_or(0, |x| x.count());
+        IterMut {
+            prefix,
+            stack: vec![IterMutStackItem {
+                iter: tree.map_or(&mut [][..], slice::from_mut).iter_mut(),
+                key_fragment: &[],
+            }],
+            length,
+        }
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> Iterator for IterMut<'a, K, V> {
+    type Item = (Vec<K>, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(IterMutStackItem { iter, .. }) = self.stack.last_mut() {
+            if let Some(tree) = iter.next() {
+                let (key, value, children) = tree.parts_mut();
+                self.stack.push(IterMutStackItem {
+                    iter: children.iter_mut(),
+                    key_fragment: key,
+                });
+                if let Some(value) = value {
+                    self.length -= 1;
+                    return Some((
+                        self.prefix
+                            .iter()
+                            .chain(self.stack.iter().flat_map(|x| x.key_fragment))
+                            .cloned()
+                            .collect(),
+                        value,
+                    ));
+                }
+            } else {
+                self.stack.pop();
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.length, Some(self.length))
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> ExactSizeIterator for IterMut<'a, K, V> {
+    fn len(&self) -> usize {
+        self.length
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> FusedIterator for IterMut<'a, K, V> {}
+
+pub struct ValuesMut<'a, K: 'a, V: 'a> {
+    inner: IterMut<'a, K, V>,
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> ExactSizeIterator for ValuesMut<'a, K, V> {
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> FusedIterator for ValuesMut<'a, K, V> {}
+
+struct IntoIterStackItem<K, V> {
+    iter: vec::IntoIter<Tree<K, V>>,
+    key_len: usize,
+}
+
+pub struct IntoIter<K, V> {
+    key: Vec<K>,
+    stack: Vec<IntoIterStackItem<K, V>>,
+    length: usize,
+}
+
+impl<K: Ord + Clone, V> IntoIter<K, V> {
+    fn new(root: Tree<K, V>) -> IntoIter<K, V> {
+        IntoIter {
+            key: vec![],
+            length: root.count(),
+            stack: vec![IntoIterStackItem {
+                iter: vec![root].into_iter(),
+                key_len: 0,
+            }],
+        }
+    }
+}
+
+impl<K: Ord + Clone, V> Iterator for IntoIter<K, V> {
+    type Item = (Vec<K>, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(IntoIterStackItem { iter, .. }) = self.stack.last_mut() {
+            if let Some(tree) = iter.next() {
+                let (key, value, children) = tree.into_parts();
+                self.key.extend(key.iter().cloned());
+                self.stack.push(IntoIterStackItem {
+                    iter: children.into_iter(),
+                    key_len: key.len(),
+                });
+                if let Some(value) = value {
+                    self.length -= 1;
+                    return Some((self.key.clone(), value));
+                }
+            } else if let Some(item) = self.stack.pop() {
+                self.key.truncate(self.key.len() - item.key_len);
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.length, Some(self.length))
+    }
+}
+
+impl<K: Ord + Clone, V> ExactSizeIterator for IntoIter<K, V> {
+    fn len(&self) -> usize {
+        self.length
+    }
+}
+
+impl<K: Ord + Clone, V> FusedIterator for IntoIter<K, V> {}
+
+pub struct Drain<'a, K: 'a, V: 'a> {
+    inner: IntoIter<K, V>,
+    marker: PhantomData<&'a mut PrefixMap<K, V>>,
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> Iterator for Drain<'a, K, V> {
+    type Item = (Vec<K>, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> ExactSizeIterator for Drain<'a, K, V> {
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> FusedIterator for Drain<'a, K, V> {}
+
+/// A difference between two maps, as yielded by [`diff`].
+///
+/// [`diff`]: struct.PrefixMap.html#method.diff
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiffItem<'a, K: 'a, V: 'a> {
+    /// The key is only in the other map.
+    Added(Vec<K>, &'a V),
+    /// The key is only in this map.
+    Removed(Vec<K>, &'a V),
+    /// The key is in both maps, with the values in this map and the other.
+    Changed(Vec<K>, &'a V, &'a V),
+}
+
+pub struct Diff<'a, K: 'a, V: 'a> {
+    inner: DiffIter<'a, K, V>,
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a + PartialEq> Iterator for Diff<'a, K, V> {
+    type Item = DiffItem<'a, K, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(key, a, b)| match (a, b) {
+            (Some(a), Some(b)) => DiffItem::Changed(key, a, b),
+            (Some(a), None) => DiffItem::Removed(key, a),
+            (None, Some(b)) => DiffItem::Added(key, b),
+            (None, None) => unreachable!(),
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
     }
 }
 
-impl<'a, K: 'a + Eq + Clone, V: 'a> FusedIterator for Values<'a, K, V> {}
+impl<'a, K: 'a + Ord + Clone, V: 'a + PartialEq> FusedIterator for Diff<'a, K, V> {}
 
-impl<K: Eq + Clone, V, Q: AsRef<[K]>> Index<Q> for PrefixMap<K, V> {
+impl<K: Ord + Clone, V, Q: AsRef<[K]>> Index<Q> for PrefixMap<K, V> {
     type Output = V;
 
     fn index(&self, index: Q) -> &Self::Output {
@@ -369,16 +3055,563 @@ impl<K: Eq + Clone, V, Q: AsRef<[K]>> Index<Q> for PrefixMap<K, V> {
     }
 }
 
-impl<K: Eq + Clone, V: Eq> PartialEq<PrefixMap<K, V>> for PrefixMap<K, V> {
+// Entries are always visited in lexicographic order, so comparing and
+// hashing them one by one doesn't depend on the order of insertion.
+impl<K: Ord + Clone, V: PartialEq> PartialEq<PrefixMap<K, V>> for PrefixMap<K, V> {
     fn eq(&self, other: &PrefixMap<K, V>) -> bool {
         self.len() == other.len() && self.iter().zip(other).all(|(a, b)| a == b)
     }
 }
 
-impl<K: Eq + Clone, V: Eq> Eq for PrefixMap<K, V> {}
+impl<K: Ord + Clone, V: Eq> Eq for PrefixMap<K, V> {}
 
-impl<K: Eq + Clone + Hash, V: Hash> Hash for PrefixMap<K, V> {
+impl<K: Ord + Clone + Hash, V: Hash> Hash for PrefixMap<K, V> {
     fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
         self.iter().for_each(|x| x.hash(state))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Key;
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::BTreeMap;
+
+    fn map_of(keys: &[Key]) -> PrefixMap<u8, usize> {
+        keys.iter().map(|x| (&x.0[..], x.0.len())).collect()
+    }
+
+    fn model_of(keys: &[Key]) -> BTreeMap<Vec<u8>, usize> {
+        keys.iter().map(|x| (x.0.clone(), x.0.len())).collect()
+    }
+
+    fn hash_of<T: Hash>(x: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        x.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[quickcheck]
+    fn prop_len_matches_model(ops: Vec<(u8, Key)>) -> bool {
+        let mut map = PrefixMap::new();
+        let mut model = BTreeMap::new();
+        for (op, Key(key)) in ops {
+            match op % 4 {
+                0 => assert_eq!(map.insert(&key, ()), model.insert(key, ())),
+                1 => assert_eq!(map.remove(&key), model.remove(&key)),
+                2 => {
+                    map.entry(&key).or_insert(());
+                    model.entry(key).or_insert(());
+                }
+                _ => {
+                    if let Entry::Occupied(entry) = map.entry(&key) {
+                        entry.remove();
+                        assert!(model.remove(&key).is_some());
+                    }
+                }
+            }
+        }
+        map.len() == model.len()
+            && map.iter().len() == model.len()
+            && map.iter().count() == model.len()
+            && map.root.is_compact()
+    }
+
+    #[quickcheck]
+    fn prop_iter_is_sorted(keys: Vec<Key>) -> bool {
+        map_of(&keys)
+            .iter()
+            .eq(model_of(&keys).iter().map(|(k, v)| (k.clone(), v)))
+    }
+
+    #[quickcheck]
+    fn prop_eq_ignores_insertion_order(keys: Vec<Key>, extra: Vec<Key>) -> bool {
+        let a = map_of(&keys);
+        let mut b = PrefixMap::new();
+        for Key(key) in extra.iter().chain(keys.iter().rev()) {
+            b.insert(key, key.len());
+        }
+        for Key(key) in &extra {
+            if !keys.iter().any(|x| x.0 == *key) {
+                b.remove(key);
+            }
+        }
+        a == b && hash_of(&a) == hash_of(&b)
+    }
+
+    #[test]
+    fn test_eq() {
+        let a = [&b"foo"[..], b"bar"]
+            .iter()
+            .map(|&x| (x, 1))
+            .collect::<PrefixMap<_, _>>();
+        let b = [&b"bar"[..], b"foo"]
+            .iter()
+            .map(|&x| (x, 1))
+            .collect::<PrefixMap<_, _>>();
+        let c = [&b"bar"[..], b"foo"]
+            .iter()
+            .map(|&x| (x, 2))
+            .collect::<PrefixMap<_, _>>();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_ne!(a, PrefixMap::new());
+    }
+
+    #[quickcheck]
+    fn prop_longest_prefix_matches_model(keys: Vec<Key>, Key(query): Key) -> bool {
+        let model = model_of(&keys);
+        let expected = (0..=query.len())
+            .rev()
+            .find(|&n| model.contains_key(&query[..n]));
+        map_of(&keys).longest_prefix(&query).map(|(n, &x)| (n, x)) == expected.map(|n| (n, n))
+    }
+
+    #[quickcheck]
+    fn prop_prefixes_of_matches_model(keys: Vec<Key>, Key(query): Key) -> bool {
+        let model = model_of(&keys);
+        let expected = (0..=query.len())
+            .filter(|&n| model.contains_key(&query[..n]))
+            .collect::<Vec<_>>();
+        map_of(&keys)
+            .prefixes_of(&query)
+            .map(|(n, _)| n)
+            .eq(expected)
+    }
+
+    #[quickcheck]
+    fn prop_iter_prefix_matches_model(keys: Vec<Key>, Key(prefix): Key) -> bool {
+        let map = map_of(&keys);
+        let iter = map.iter_prefix(&prefix);
+        let expected = model_of(&keys)
+            .into_iter()
+            .filter(|x| x.0.starts_with(&prefix))
+            .collect::<Vec<_>>();
+        iter.len() == expected.len() && iter.map(|(k, &v)| (k, v)).eq(expected)
+    }
+
+    #[quickcheck]
+    fn prop_subtrie_matches_model(keys: Vec<Key>, Key(prefix): Key, Key(inner): Key) -> bool {
+        let map = map_of(&keys);
+        let model = model_of(&keys)
+            .into_iter()
+            .filter(|x| x.0.starts_with(&prefix))
+            .map(|(k, v)| (k[prefix.len()..].to_vec(), v))
+            .collect::<BTreeMap<_, _>>();
+        let sub = match map.subtrie(&prefix) {
+            Some(sub) => sub,
+            None => return model.is_empty(),
+        };
+        let expected = model
+            .clone()
+            .into_iter()
+            .filter(|x| x.0.starts_with(&inner))
+            .collect::<Vec<_>>();
+        sub.len() == model.len()
+            && sub.iter().map(|(k, &v)| (k, v)).eq(model.clone())
+            && sub.iter_prefix(&inner).map(|(k, &v)| (k, v)).eq(expected)
+            && model.iter().all(|(k, v)| sub.get(k) == Some(v))
+            && sub.get(&inner) == model.get(&inner)
+    }
+
+    #[quickcheck]
+    fn prop_remove_prefix_matches_model(keys: Vec<Key>, Key(prefix): Key) -> bool {
+        let mut map = map_of(&keys);
+        let (removed, kept): (BTreeMap<_, _>, BTreeMap<_, _>) = model_of(&keys)
+            .into_iter()
+            .partition(|x| x.0.starts_with(&prefix));
+        let detached = map.remove_prefix(&prefix);
+        detached.len() == removed.len()
+            && map.len() == kept.len()
+            && detached.iter().map(|(k, &v)| (k, v)).eq(removed.clone())
+            && map.iter().map(|(k, &v)| (k, v)).eq(kept.clone())
+            && detached.root.is_compact()
+            && map.root.is_compact()
+    }
+
+    #[quickcheck]
+    fn prop_count_prefix_matches_model(keys: Vec<Key>, Key(prefix): Key) -> bool {
+        let expected = model_of(&keys)
+            .keys()
+            .filter(|x| x.starts_with(&prefix))
+            .count();
+        map_of(&keys).count_prefix(&prefix) == expected
+    }
+
+    fn bound_of(kind: u8, key: &[u8]) -> Bound<&[u8]> {
+        match kind % 3 {
+            0 => Bound::Included(key),
+            1 => Bound::Excluded(key),
+            _ => Bound::Unbounded,
+        }
+    }
+
+    #[quickcheck]
+    fn prop_range_matches_model(
+        keys: Vec<Key>,
+        (start, Key(lo)): (u8, Key),
+        (end, Key(hi)): (u8, Key),
+        turns: Vec<bool>,
+    ) -> bool {
+        let bounds = (bound_of(start, &lo), bound_of(end, &hi));
+        let map = map_of(&keys);
+        let expected: Vec<_> = model_of(&keys)
+            .into_iter()
+            .filter(|(k, _)| bounds.contains(&k[..]))
+            .collect();
+        let mut range = map.range::<&[u8], _>(bounds);
+        if range.len() != expected.len() {
+            return false;
+        }
+        // Take entries from both ends in a random order.
+        let (mut front, mut back) = (vec![], vec![]);
+        for &turn in turns.iter().chain(Some(&true)).cycle().take(expected.len()) {
+            let next = if turn {
+                range.next()
+            } else {
+                range.next_back()
+            };
+            match next {
+                Some((k, &v)) if turn => front.push((k, v)),
+                Some((k, &v)) => back.push((k, v)),
+                None => return false,
+            }
+        }
+        front.extend(back.into_iter().rev());
+        range.next().is_none() && range.next_back().is_none() && front == expected
+    }
+
+    #[quickcheck]
+    fn prop_neighbours_match_model(keys: Vec<Key>, Key(key): Key) -> bool {
+        let map = map_of(&keys);
+        let model = model_of(&keys);
+        let owned = |x: Option<(Vec<u8>, &usize)>| x.map(|(k, &v)| (k, v));
+        let expected = |x: Option<(&Vec<u8>, &usize)>| x.map(|(k, &v)| (k.clone(), v));
+        owned(map.first_key_value()) == expected(model.iter().next())
+            && owned(map.last_key_value()) == expected(model.iter().next_back())
+            && owned(map.predecessor(&key)) == expected(model.range(..key.clone()).next_back())
+            && owned(map.successor(&key))
+                == expected(
+                    model
+                        .range((Bound::Excluded(key.clone()), Bound::Unbounded))
+                        .next(),
+                )
+    }
+
+    #[quickcheck]
+    fn prop_pop_matches_model(keys: Vec<Key>, from_back: Vec<bool>) -> bool {
+        let mut map = map_of(&keys);
+        let mut model = model_of(&keys);
+        from_back.into_iter().all(|back| {
+            let popped = if back {
+                map.pop_last()
+            } else {
+                map.pop_first()
+            };
+            let expected = if back {
+                model.keys().next_back().cloned()
+            } else {
+                model.keys().next().cloned()
+            }
+            .map(|k| {
+                let v = model.remove(&k).unwrap();
+                (k, v)
+            });
+            popped == expected && map.len() == model.len() && map.root.is_compact()
+        })
+    }
+
+    #[quickcheck]
+    fn prop_rank_and_select_match_model(keys: Vec<Key>, Key(key): Key) -> bool {
+        let map = map_of(&keys);
+        let model = model_of(&keys);
+        map.rank(&key) == model.range(..key).count()
+            && model
+                .iter()
+                .enumerate()
+                .all(|(i, (k, v))| map.select(i) == Some((k.clone(), v)))
+            && map.select(model.len()).is_none()
+    }
+
+    #[quickcheck]
+    fn prop_iter_prefix_mut_matches_model(keys: Vec<Key>, Key(prefix): Key) -> bool {
+        let mut map = map_of(&keys);
+        let mut model = model_of(&keys);
+        let keys: Vec<_> = map
+            .iter_prefix_mut(&prefix)
+            .map(|(k, v)| {
+                *v += 1;
+                k
+            })
+            .collect();
+        model
+            .iter_mut()
+            .filter(|(k, _)| k.starts_with(&prefix))
+            .for_each(|(_, v)| *v += 1);
+        keys == map.keys_with_prefix(&prefix).collect::<Vec<_>>()
+            && map.iter().eq(model.iter().map(|(k, v)| (k.clone(), v)))
+    }
+
+    #[quickcheck]
+    fn prop_values_mut_matches_model(keys: Vec<Key>) -> bool {
+        let mut map = map_of(&keys);
+        let mut model = model_of(&keys);
+        let values = map.values_mut();
+        let hint = values.size_hint();
+        values.for_each(|v| *v += 1);
+        model.values_mut().for_each(|v| *v += 1);
+        hint == (model.len(), Some(model.len()))
+            && map.iter().eq(model.iter().map(|(k, v)| (k.clone(), v)))
+    }
+
+    #[quickcheck]
+    fn prop_into_iter_matches_model(keys: Vec<Key>) -> bool {
+        let map = map_of(&keys);
+        let model = model_of(&keys);
+        map.into_iter().eq(model)
+    }
+
+    #[quickcheck]
+    fn prop_retain_matches_model(keys: Vec<Key>, Key(drop): Key) -> bool {
+        let mut map = map_of(&keys);
+        let mut model = model_of(&keys);
+        let mut visited = vec![];
+        map.retain(|k, v| {
+            visited.push(k.to_vec());
+            *v += 1;
+            !k.starts_with(&drop)
+        });
+        let expected_visits: Vec<_> = model.keys().cloned().collect();
+        model.retain(|k, _| !k.starts_with(&drop));
+        model.values_mut().for_each(|v| *v += 1);
+        visited == expected_visits
+            && map.len() == model.len()
+            && map.root.is_compact()
+            && map.iter().eq(model.iter().map(|(k, v)| (k.clone(), v)))
+    }
+
+    #[quickcheck]
+    fn prop_for_each_entry_matches_iter(keys: Vec<Key>) -> bool {
+        let map = map_of(&keys);
+        let mut entries = vec![];
+        map.for_each_entry(|k, v| entries.push((k.to_vec(), v)));
+        map.iter().eq(entries)
+    }
+
+    #[quickcheck]
+    fn prop_cursor_matches_model(keys: Vec<Key>, Key(key): Key) -> bool {
+        let map = map_of(&keys);
+        let model = model_of(&keys);
+        let mut cursor = map.cursor();
+        for n in 0..=key.len() {
+            let walked = &key[..n];
+            let longer = model.keys().any(|k| k.len() > n && k.starts_with(walked));
+            if cursor.value() != model.get(walked) || cursor.has_children() != longer {
+                return false;
+            }
+            if let Some(k) = key.get(n) {
+                let can_extend = model.keys().any(|x| x.starts_with(&key[..=n]));
+                if cursor.can_extend(k) != can_extend || cursor.advance(k) != can_extend {
+                    return false;
+                }
+                if !can_extend {
+                    break;
+                }
+            }
+        }
+        cursor.reset();
+        cursor.value() == model.get(&vec![])
+    }
+
+    #[quickcheck]
+    fn prop_cursor_mut_matches_model(keys: Vec<Key>, ops: Vec<(u8, Key)>) -> bool {
+        let mut map = map_of(&keys);
+        let mut model = model_of(&keys);
+        let mut cursor = map.cursor_mut();
+        for (op, Key(arg)) in ops {
+            let at = cursor.key().to_vec();
+            let ok = match op % 6 {
+                0 => arg.iter().all(|k| {
+                    let mut next = cursor.key().to_vec();
+                    next.push(*k);
+                    let expected = model.keys().any(|x| x.starts_with(&next));
+                    if !expected {
+                        next.pop();
+                    }
+                    cursor.advance(k) == expected && cursor.key() == &next[..]
+                }),
+                1 => {
+                    let mut key = at.clone();
+                    key.extend(&arg);
+                    cursor.insert(&arg, arg.len()) == model.insert(key, arg.len())
+                }
+                2 => cursor.set_value(at.len()) == model.insert(at.clone(), at.len()),
+                3 => cursor.take_value() == model.remove(&at),
+                4 => {
+                    let removed = cursor.remove_subtree();
+                    let (expected, rest): (BTreeMap<_, _>, _) = mem::take(&mut model)
+                        .into_iter()
+                        .partition(|(k, _)| k.starts_with(&at));
+                    model = rest;
+                    removed
+                        .iter()
+                        .eq(expected.iter().map(|(k, v)| (k.clone(), v)))
+                }
+                _ => {
+                    cursor.reset();
+                    cursor.key().is_empty()
+                }
+            };
+            // After removals, the cursor keeps the longest prefix of its key
+            // that is still part of some key.
+            let expected_at = (0..=at.len())
+                .rev()
+                .map(|n| &at[..n])
+                .find(|p| p.is_empty() || model.keys().any(|k| k.starts_with(p)))
+                .unwrap();
+            if !ok
+                || (op % 6 == 3 || op % 6 == 4) && cursor.key() != expected_at
+                || cursor.value() != model.get(cursor.key())
+            {
+                return false;
+            }
+        }
+        drop(cursor);
+        map.root.is_compact() && map.iter().eq(model.iter().map(|(k, v)| (k.clone(), v)))
+    }
+
+    #[quickcheck]
+    fn prop_iter_is_double_ended(keys: Vec<Key>, Key(prefix): Key, turns: Vec<bool>) -> bool {
+        let map = map_of(&keys);
+        let expected: Vec<_> = model_of(&keys)
+            .into_iter()
+            .filter_map(|(k, v)| k.strip_prefix(&prefix[..]).map(|k| (k.to_vec(), v)))
+            .collect();
+        let mut iter = match map.subtrie(&prefix) {
+            Some(subtrie) => subtrie.iter(),
+            None => return expected.is_empty(),
+        };
+        let (mut front, mut back) = (vec![], vec![]);
+        for &turn in turns.iter().chain(Some(&true)).cycle().take(expected.len()) {
+            match if turn { iter.next() } else { iter.next_back() } {
+                Some((k, &v)) if turn => front.push((k, v)),
+                Some((k, &v)) => back.push((k, v)),
+                None => return false,
+            }
+            if iter.len() != expected.len() - front.len() - back.len() {
+                return false;
+            }
+        }
+        front.extend(back.into_iter().rev());
+        iter.next().is_none() && iter.next_back().is_none() && front == expected
+    }
+
+    #[quickcheck]
+    fn prop_keys_and_values_match_model(keys: Vec<Key>) -> bool {
+        let map = map_of(&keys);
+        let model = model_of(&keys);
+        let exact = (model.len(), Some(model.len()));
+        map.keys().size_hint() == exact
+            && map.values().size_hint() == exact
+            && map.keys().rev().eq(model.keys().rev().cloned())
+            && map.values().eq(model.values())
+    }
+
+    #[quickcheck]
+    fn prop_key_value_lookups_match_model(keys: Vec<Key>, Key(key): Key) -> bool {
+        let map = map_of(&keys);
+        let model = model_of(&keys);
+        let longest = (0..=key.len())
+            .rev()
+            .find_map(|n| model.get_key_value(&key[..n]))
+            .map(|(k, v)| (k.clone(), v));
+        map.get_key_value(&key) == model.get_key_value(&key).map(|(k, v)| (k.clone(), v))
+            && map.longest_prefix_key_value(&key) == longest
+    }
+
+    #[quickcheck]
+    fn prop_merge_with_matches_model(a: Vec<Key>, b: Vec<Key>) -> bool {
+        let mut map = map_of(&a);
+        let mut model = model_of(&a);
+        let other: PrefixMap<u8, usize> = b.iter().map(|x| (&x.0[..], 10)).collect();
+        let mut collisions = vec![];
+        map.merge_with(other, |k, x, y| {
+            collisions.push(k.to_vec());
+            x + y
+        });
+        let mut expected_collisions = vec![];
+        for k in model_of(&b).into_keys() {
+            if model.contains_key(&k) {
+                expected_collisions.push(k.clone());
+            }
+            *model.entry(k).or_insert(0) += 10;
+        }
+        collisions == expected_collisions
+            && map.len() == model.len()
+            && map.root.is_compact()
+            && map.iter().eq(model.iter().map(|(k, v)| (k.clone(), v)))
+    }
+
+    #[quickcheck]
+    fn prop_diff_matches_model(a: Vec<Key>, b: Vec<(Key, bool)>) -> bool {
+        let old = map_of(&a);
+        // Flagged keys get a value unlike the one in `old`, so they change.
+        let new: PrefixMap<u8, usize> = b
+            .iter()
+            .map(|(Key(k), change)| (&k[..], k.len() + (*change as usize)))
+            .collect();
+        let (old_model, new_model) = (
+            model_of(&a),
+            new.iter().map(|(k, &v)| (k, v)).collect::<BTreeMap<_, _>>(),
+        );
+        let mut keys: Vec<_> = old_model.keys().chain(new_model.keys()).collect();
+        keys.sort();
+        keys.dedup();
+        let expected =
+            keys.into_iter()
+                .filter_map(|k| match (old_model.get(k), new_model.get(k)) {
+                    (Some(x), Some(y)) if x == y => None,
+                    (Some(x), Some(y)) => Some(DiffItem::Changed(k.clone(), x, y)),
+                    (Some(x), None) => Some(DiffItem::Removed(k.clone(), x)),
+                    (None, Some(y)) => Some(DiffItem::Added(k.clone(), y)),
+                    (None, None) => None,
+                });
+        old.diff(&new).eq(expected)
+    }
+
+    #[quickcheck]
+    fn prop_split_off_matches_model(keys: Vec<Key>, Key(key): Key) -> bool {
+        let mut map = map_of(&keys);
+        let mut model = model_of(&keys);
+        let tail = map.split_off(&key);
+        let model_tail = model.split_off(&key);
+        map.root.is_compact()
+            && tail.root.is_compact()
+            && map.len() == model.len()
+            && tail.len() == model_tail.len()
+            && map.into_iter().eq(model)
+            && tail.into_iter().eq(model_tail)
+    }
+
+    #[quickcheck]
+    fn prop_rename_prefix_matches_model(keys: Vec<Key>, Key(from): Key, Key(to): Key) -> bool {
+        let mut map = map_of(&keys);
+        let mut model = model_of(&keys);
+        let moved = map.rename_prefix(&from, &to, |_, existing, _| existing);
+        let (inside, mut expected): (BTreeMap<_, _>, BTreeMap<_, _>) =
+            model.into_iter().partition(|(k, _)| k.starts_with(&from));
+        let expected_moved = inside.len();
+        for (k, v) in inside {
+            let mut key = to.clone();
+            key.extend_from_slice(&k[from.len()..]);
+            expected.entry(key).or_insert(v);
+        }
+        model = expected;
+        moved == expected_moved
+            && map.len() == model.len()
+            && map.root.is_compact()
+            && map.into_iter().eq(model)
+    }
+}
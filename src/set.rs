@@ -1,6 +1,12 @@
-use map::{Iter as MapIter, PrefixMap};
+//! A set implemented as a `PrefixMap` where the value is `()`.
+
+use map::{
+    Drain as MapDrain, IntoIter as MapIntoIter, Iter as MapIter, PrefixMap, Range as MapRange,
+};
 use std::hash::{Hash, Hasher};
 use std::iter::{FromIterator, FusedIterator};
+use std::ops::{BitAnd, BitOr, BitXor, RangeBounds, Sub};
+use tree::SetOp;
 
 /// A set implemented as a `PrefixMap` where the value is `()`.
 #[derive(Clone, Debug, Default)]
@@ -8,7 +14,7 @@ pub struct PrefixSet<T> {
     map: PrefixMap<T, ()>,
 }
 
-impl<T: Eq + Clone> PrefixSet<T> {
+impl<T: Ord + Clone> PrefixSet<T> {
     /// Creates an empty `PrefixSet`.
     ///
     /// # Examples
@@ -40,6 +46,49 @@ impl<T: Eq + Clone> PrefixSet<T> {
         self.map.clear()
     }
 
+    /// Clears the set, returning all values as an iterator, in lexicographic
+    /// order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixSet;
+    ///
+    /// let mut set: PrefixSet<u8> = PrefixSet::new();
+    /// set.insert("foo");
+    /// set.insert("bar");
+    /// let drained: Vec<_> = set.drain().collect();
+    /// assert_eq!(drained, vec![b"bar".to_vec(), b"foo".to_vec()]);
+    /// assert!(set.is_empty());
+    /// ```
+    pub fn drain(&mut self) -> Drain<'_, T> {
+        Drain {
+            iter: self.map.drain(),
+        }
+    }
+
+    /// Retains only the values for which the predicate returns `true`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixSet;
+    ///
+    /// let mut set: PrefixSet<u8> = PrefixSet::new();
+    /// set.insert("foo");
+    /// set.insert("foobar");
+    /// set.insert("bar");
+    /// set.retain(|x| x.starts_with(b"foo"));
+    /// assert!(!set.contains("bar"));
+    /// assert_eq!(set.len(), 2);
+    /// ```
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&[T]) -> bool,
+    {
+        self.map.retain(|k, _| f(k))
+    }
+
     /// Returns `true` if the set contains a value.
     ///
     /// # Examples
@@ -59,6 +108,25 @@ impl<T: Eq + Clone> PrefixSet<T> {
         self.map.get(key).is_some()
     }
 
+    /// Returns the value stored in the set which is equal to the given one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixSet;
+    ///
+    /// let mut set: PrefixSet<u8> = PrefixSet::new();
+    /// set.insert("1");
+    /// assert_eq!(set.get("1"), Some(b"1".to_vec()));
+    /// assert_eq!(set.get("2"), None);
+    /// ```
+    pub fn get<Q>(&self, key: Q) -> Option<Vec<T>>
+    where
+        Q: AsRef<[T]>,
+    {
+        self.map.get_key_value(key).map(|(k, _)| k)
+    }
+
     /// Adds a value to the set.
     ///
     /// # Examples
@@ -98,6 +166,164 @@ impl<T: Eq + Clone> PrefixSet<T> {
         self.map.remove(key).is_some()
     }
 
+    /// Removes every value starting with the given prefix from the set,
+    /// returning them in a new set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixSet;
+    ///
+    /// let mut set: PrefixSet<u8> = PrefixSet::new();
+    /// set.insert("foo");
+    /// set.insert("foobar");
+    /// set.insert("bar");
+    ///
+    /// let removed = set.remove_prefix("fo");
+    /// assert_eq!(removed.len(), 2);
+    /// assert_eq!(removed.contains("foobar"), true);
+    /// assert_eq!(set.len(), 1);
+    /// assert_eq!(set.contains("foo"), false);
+    /// ```
+    pub fn remove_prefix<Q>(&mut self, prefix: Q) -> PrefixSet<T>
+    where
+        Q: AsRef<[T]>,
+    {
+        PrefixSet {
+            map: self.map.remove_prefix(prefix),
+        }
+    }
+
+    /// Returns a new set with the values which are in `self`, `other`, or
+    /// both.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixSet;
+    ///
+    /// let a: PrefixSet<u8> = vec![&b"ab"[..], b"abc"].into_iter().collect();
+    /// let b: PrefixSet<u8> = vec![&b"abc"[..], b"b"].into_iter().collect();
+    /// let union = a.union(&b);
+    /// assert_eq!(
+    ///     union.iter().collect::<Vec<_>>(),
+    ///     vec![b"ab".to_vec(), b"abc".to_vec(), b"b".to_vec()]
+    /// );
+    /// ```
+    pub fn union(&self, other: &PrefixSet<T>) -> PrefixSet<T> {
+        PrefixSet {
+            map: self.map.combine(&other.map, SetOp::Union),
+        }
+    }
+
+    /// Returns a new set with the values which are in both `self` and
+    /// `other`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixSet;
+    ///
+    /// let a: PrefixSet<u8> = vec![&b"ab"[..], b"abc"].into_iter().collect();
+    /// let b: PrefixSet<u8> = vec![&b"abc"[..], b"b"].into_iter().collect();
+    /// let intersection = a.intersection(&b);
+    /// assert_eq!(intersection.iter().collect::<Vec<_>>(), vec![b"abc".to_vec()]);
+    /// ```
+    pub fn intersection(&self, other: &PrefixSet<T>) -> PrefixSet<T> {
+        PrefixSet {
+            map: self.map.combine(&other.map, SetOp::Intersection),
+        }
+    }
+
+    /// Returns a new set with the values which are in `self` but not in
+    /// `other`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixSet;
+    ///
+    /// let a: PrefixSet<u8> = vec![&b"ab"[..], b"abc"].into_iter().collect();
+    /// let b: PrefixSet<u8> = vec![&b"abc"[..], b"b"].into_iter().collect();
+    /// let difference = a.difference(&b);
+    /// assert_eq!(difference.iter().collect::<Vec<_>>(), vec![b"ab".to_vec()]);
+    /// ```
+    pub fn difference(&self, other: &PrefixSet<T>) -> PrefixSet<T> {
+        PrefixSet {
+            map: self.map.combine(&other.map, SetOp::Difference),
+        }
+    }
+
+    /// Returns a new set with the values which are in either `self` or
+    /// `other`, but not in both.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixSet;
+    ///
+    /// let a: PrefixSet<u8> = vec![&b"ab"[..], b"abc"].into_iter().collect();
+    /// let b: PrefixSet<u8> = vec![&b"abc"[..], b"b"].into_iter().collect();
+    /// let difference = a.symmetric_difference(&b);
+    /// assert_eq!(
+    ///     difference.iter().collect::<Vec<_>>(),
+    ///     vec![b"ab".to_vec(), b"b".to_vec()]
+    /// );
+    /// ```
+    pub fn symmetric_difference(&self, other: &PrefixSet<T>) -> PrefixSet<T> {
+        PrefixSet {
+            map: self.map.combine(&other.map, SetOp::SymmetricDifference),
+        }
+    }
+
+    /// Returns `true` if every value of `self` is in `other`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixSet;
+    ///
+    /// let a: PrefixSet<u8> = vec![&b"ab"[..]].into_iter().collect();
+    /// let b: PrefixSet<u8> = vec![&b"ab"[..], b"b"].into_iter().collect();
+    /// assert!(a.is_subset(&b));
+    /// assert!(!b.is_subset(&a));
+    /// ```
+    pub fn is_subset(&self, other: &PrefixSet<T>) -> bool {
+        self.len() <= other.len() && !self.map.combines_any(&other.map, SetOp::Difference)
+    }
+
+    /// Returns `true` if every value of `other` is in `self`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixSet;
+    ///
+    /// let a: PrefixSet<u8> = vec![&b"ab"[..]].into_iter().collect();
+    /// let b: PrefixSet<u8> = vec![&b"ab"[..], b"b"].into_iter().collect();
+    /// assert!(b.is_superset(&a));
+    /// assert!(!a.is_superset(&b));
+    /// ```
+    pub fn is_superset(&self, other: &PrefixSet<T>) -> bool {
+        other.is_subset(self)
+    }
+
+    /// Returns `true` if `self` and `other` have no values in common.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixSet;
+    ///
+    /// let a: PrefixSet<u8> = vec![&b"ab"[..]].into_iter().collect();
+    /// let b: PrefixSet<u8> = vec![&b"abc"[..], b"b"].into_iter().collect();
+    /// assert!(a.is_disjoint(&b));
+    /// assert!(!a.is_disjoint(&a));
+    /// ```
+    pub fn is_disjoint(&self, other: &PrefixSet<T>) -> bool {
+        !self.map.combines_any(&other.map, SetOp::Intersection)
+    }
+
     /// Returns `true` if the set contains no elements.
     ///
     /// # Examples
@@ -130,7 +356,7 @@ impl<T: Eq + Clone> PrefixSet<T> {
         self.map.len()
     }
 
-    /// Gets an iterator that visits the values in `PrefixSet`.
+    /// Gets an iterator that visits the values in `PrefixSet`, in lexicographic order.
     ///
     /// # Examples
     ///
@@ -138,20 +364,73 @@ impl<T: Eq + Clone> PrefixSet<T> {
     /// use prefix_tree::PrefixSet;
     ///
     /// let mut set: PrefixSet<u8> = PrefixSet::new();
-    /// set.insert("1");
     /// set.insert("2");
+    /// set.insert("1");
     /// let mut iter = set.iter();
     /// assert_eq!(iter.next(), Some(vec![b'1']));
     /// assert_eq!(iter.next(), Some(vec![b'2']));
     /// ```
-    pub fn iter(&self) -> Iter<T> {
+    pub fn iter(&self) -> Iter<'_, T> {
         Iter {
             iter: self.map.iter(),
         }
     }
+
+    /// Gets an iterator that visits the values in `PrefixSet` which start
+    /// with the given prefix, in lexicographic order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixSet;
+    ///
+    /// let mut set: PrefixSet<u8> = PrefixSet::new();
+    /// set.insert("foo");
+    /// set.insert("foobar");
+    /// set.insert("bar");
+    /// let mut iter = set.iter_prefix("fo");
+    /// assert_eq!(iter.next(), Some(b"foo".to_vec()));
+    /// assert_eq!(iter.next(), Some(b"foobar".to_vec()));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    pub fn iter_prefix<Q>(&self, prefix: Q) -> Iter<'_, T>
+    where
+        Q: AsRef<[T]>,
+    {
+        Iter {
+            iter: self.map.iter_prefix(prefix),
+        }
+    }
+
+    /// Gets an iterator that visits the values in `PrefixSet` which fall
+    /// within the given range, in lexicographic order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prefix_tree::PrefixSet;
+    ///
+    /// let mut set: PrefixSet<u8> = PrefixSet::new();
+    /// set.insert("bar");
+    /// set.insert("baz");
+    /// set.insert("foo");
+    /// let mut range = set.range("bar".."foo");
+    /// assert_eq!(range.next(), Some(b"bar".to_vec()));
+    /// assert_eq!(range.next_back(), Some(b"baz".to_vec()));
+    /// assert_eq!(range.next(), None);
+    /// ```
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, T>
+    where
+        Q: AsRef<[T]>,
+        R: RangeBounds<Q>,
+    {
+        Range {
+            iter: self.map.range(range),
+        }
+    }
 }
 
-impl<'a, T: 'a + Eq + Clone> FromIterator<&'a [T]> for PrefixSet<T> {
+impl<'a, T: 'a + Ord + Clone> FromIterator<&'a [T]> for PrefixSet<T> {
     fn from_iter<I>(iter: I) -> PrefixSet<T>
     where
         I: IntoIterator<Item = &'a [T]>,
@@ -164,7 +443,19 @@ impl<'a, T: 'a + Eq + Clone> FromIterator<&'a [T]> for PrefixSet<T> {
     }
 }
 
-impl<'a, T: 'a + Eq + Clone> IntoIterator for &'a PrefixSet<T> {
+impl<T: Ord + Clone> IntoIterator for PrefixSet<T> {
+    type Item = Vec<T>;
+
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            iter: self.map.into_iter(),
+        }
+    }
+}
+
+impl<'a, T: 'a + Ord + Clone> IntoIterator for &'a PrefixSet<T> {
     type Item = Vec<T>;
 
     type IntoIter = Iter<'a, T>;
@@ -178,32 +469,216 @@ pub struct Iter<'a, T> {
     iter: MapIter<'a, T, ()>,
 }
 
-impl<'a, T: 'a + Eq + Clone> Iterator for Iter<'a, T> {
+impl<'a, T: 'a + Ord + Clone> Iterator for Iter<'a, T> {
     type Item = Vec<T>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.iter.next().map(|(k, _)| k)
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
 }
 
-impl<'a, T: 'a + Eq + Clone> ExactSizeIterator for Iter<'a, T> {
+impl<'a, T: 'a + Ord + Clone> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back().map(|(k, _)| k)
+    }
+}
+
+impl<'a, T: 'a + Ord + Clone> ExactSizeIterator for Iter<'a, T> {
     fn len(&self) -> usize {
         self.iter.len()
     }
 }
 
-impl<'a, T: 'a + Eq + Clone> FusedIterator for Iter<'a, T> {}
+impl<'a, T: 'a + Ord + Clone> FusedIterator for Iter<'a, T> {}
+
+pub struct IntoIter<T> {
+    iter: MapIntoIter<T, ()>,
+}
+
+impl<T: Ord + Clone> Iterator for IntoIter<T> {
+    type Item = Vec<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T: Ord + Clone> ExactSizeIterator for IntoIter<T> {
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+impl<T: Ord + Clone> FusedIterator for IntoIter<T> {}
+
+pub struct Drain<'a, T: 'a> {
+    iter: MapDrain<'a, T, ()>,
+}
+
+impl<'a, T: 'a + Ord + Clone> Iterator for Drain<'a, T> {
+    type Item = Vec<T>;
 
-impl<T: Eq + Clone> PartialEq<PrefixSet<T>> for PrefixSet<T> {
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, T: 'a + Ord + Clone> ExactSizeIterator for Drain<'a, T> {
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+impl<'a, T: 'a + Ord + Clone> FusedIterator for Drain<'a, T> {}
+
+pub struct Range<'a, T> {
+    iter: MapRange<'a, T, ()>,
+}
+
+impl<'a, T: 'a + Ord + Clone> Iterator for Range<'a, T> {
+    type Item = Vec<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, T: 'a + Ord + Clone> DoubleEndedIterator for Range<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back().map(|(k, _)| k)
+    }
+}
+
+impl<'a, T: 'a + Ord + Clone> ExactSizeIterator for Range<'a, T> {
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+impl<'a, T: 'a + Ord + Clone> FusedIterator for Range<'a, T> {}
+
+impl<T: Ord + Clone> BitOr<&PrefixSet<T>> for &PrefixSet<T> {
+    type Output = PrefixSet<T>;
+
+    fn bitor(self, rhs: &PrefixSet<T>) -> PrefixSet<T> {
+        self.union(rhs)
+    }
+}
+
+impl<T: Ord + Clone> BitAnd<&PrefixSet<T>> for &PrefixSet<T> {
+    type Output = PrefixSet<T>;
+
+    fn bitand(self, rhs: &PrefixSet<T>) -> PrefixSet<T> {
+        self.intersection(rhs)
+    }
+}
+
+impl<T: Ord + Clone> Sub<&PrefixSet<T>> for &PrefixSet<T> {
+    type Output = PrefixSet<T>;
+
+    fn sub(self, rhs: &PrefixSet<T>) -> PrefixSet<T> {
+        self.difference(rhs)
+    }
+}
+
+impl<T: Ord + Clone> BitXor<&PrefixSet<T>> for &PrefixSet<T> {
+    type Output = PrefixSet<T>;
+
+    fn bitxor(self, rhs: &PrefixSet<T>) -> PrefixSet<T> {
+        self.symmetric_difference(rhs)
+    }
+}
+
+impl<T: Ord + Clone> PartialEq<PrefixSet<T>> for PrefixSet<T> {
     fn eq(&self, other: &PrefixSet<T>) -> bool {
-        self.len() == other.len() && self.iter().zip(other).all(|(a, b)| a == b)
+        self.map == other.map
     }
 }
 
-impl<T: Eq + Clone> Eq for PrefixSet<T> {}
+impl<T: Ord + Clone> Eq for PrefixSet<T> {}
 
-impl<T: Eq + Clone + Hash> Hash for PrefixSet<T> {
+impl<T: Ord + Clone + Hash> Hash for PrefixSet<T> {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.iter().for_each(|x| x.hash(state))
+        self.map.hash(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Key;
+    use std::collections::BTreeSet;
+
+    fn set_of(keys: &[Key]) -> PrefixSet<u8> {
+        keys.iter().map(|x| &x.0[..]).collect()
+    }
+
+    fn model_of(keys: &[Key]) -> BTreeSet<Vec<u8>> {
+        keys.iter().map(|x| x.0.clone()).collect()
+    }
+
+    fn same(set: &PrefixSet<u8>, model: BTreeSet<Vec<u8>>) -> bool {
+        set.len() == model.len() && set.iter().eq(model)
+    }
+
+    #[quickcheck]
+    fn prop_range_matches_model(keys: Vec<Key>, Key(start): Key, Key(end): Key) -> bool {
+        let set = set_of(&keys);
+        let expected: Vec<_> = model_of(&keys)
+            .into_iter()
+            .filter(|x| *x >= start && *x < end)
+            .collect();
+        let range = set.range(&start[..]..&end[..]);
+        range.size_hint() == (expected.len(), Some(expected.len())) && range.eq(expected)
+    }
+
+    #[quickcheck]
+    fn prop_owning_iterators_match_model(keys: Vec<Key>) -> bool {
+        let model = model_of(&keys);
+        let exact = (model.len(), Some(model.len()));
+        let mut set = set_of(&keys);
+        let drain = set.drain();
+        let drained = drain.size_hint() == exact && drain.eq(model.iter().cloned());
+        let into_iter = set_of(&keys).into_iter();
+        drained && set.is_empty() && into_iter.size_hint() == exact && into_iter.eq(model)
+    }
+
+    #[quickcheck]
+    fn prop_iter_matches_model(keys: Vec<Key>) -> bool {
+        let set = set_of(&keys);
+        let model = model_of(&keys);
+        set.iter().size_hint() == (model.len(), Some(model.len()))
+            && set.iter().eq(model.iter().cloned())
+            && set.iter().rev().eq(model.iter().rev().cloned())
+    }
+
+    #[quickcheck]
+    fn prop_set_algebra_matches_model(a: Vec<Key>, b: Vec<Key>) -> bool {
+        let (x, y) = (set_of(&a), set_of(&b));
+        let (a, b) = (model_of(&a), model_of(&b));
+        same(&(&x | &y), &a | &b)
+            && same(&(&x & &y), &a & &b)
+            && same(&(&x - &y), &a - &b)
+            && same(&(&x ^ &y), &a ^ &b)
+            && x.is_subset(&y) == a.is_subset(&b)
+            && x.is_superset(&y) == a.is_superset(&b)
+            && x.is_disjoint(&y) == a.is_disjoint(&b)
+            && (&x & &x).is_subset(&x)
     }
 }
@@ -0,0 +1,12 @@
+use quickcheck::{Arbitrary, Gen};
+
+/// A short key over a small alphabet, so that random keys share prefixes.
+#[derive(Clone, Debug)]
+pub struct Key(pub Vec<u8>);
+
+impl Arbitrary for Key {
+    fn arbitrary<G: Gen>(g: &mut G) -> Key {
+        let len = usize::arbitrary(g) % 6;
+        Key((0..len).map(|_| u8::arbitrary(g) % 3).collect())
+    }
+}
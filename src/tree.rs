@@ -1,6 +1,9 @@
+use std::cmp::Ordering;
 use std::mem;
+use std::ptr;
+use std::slice;
 
-fn common_prefix<T: Eq>(a: &[T], b: &[T]) -> usize {
+pub fn common_prefix<T: Eq>(a: &[T], b: &[T]) -> usize {
     a.iter().zip(b).take_while(|&(a, b)| a == b).count()
 }
 
@@ -9,14 +12,16 @@ pub struct Tree<K, V> {
     key: Vec<K>,
     value: Option<V>,
     children: Vec<Tree<K, V>>,
+    count: usize,
 }
 
-impl<K: Eq + Clone, V> Tree<K, V> {
+impl<K: Ord + Clone, V> Tree<K, V> {
     pub fn new(key: Vec<K>, value: V) -> Tree<K, V> {
         Tree {
             key,
             value: Some(value),
             children: vec![],
+            count: 1,
         }
     }
 
@@ -25,6 +30,20 @@ impl<K: Eq + Clone, V> Tree<K, V> {
             key: vec![],
             value: None,
             children: vec![],
+            count: 0,
+        }
+    }
+
+    /// Creates a root holding `subtree`, whose key is relative to the root.
+    pub fn from_subtree(subtree: Tree<K, V>) -> Tree<K, V> {
+        if subtree.key.is_empty() {
+            return subtree;
+        }
+        Tree {
+            key: vec![],
+            value: None,
+            count: subtree.count,
+            children: vec![subtree],
         }
     }
 
@@ -32,6 +51,14 @@ impl<K: Eq + Clone, V> Tree<K, V> {
         &self.key
     }
 
+    /// Replaces the first `n` elements of the key with `prefix`. A detached
+    /// root may hold no value and a single child, so the node is merged with
+    /// it, or dropped if it's empty.
+    pub fn with_key_prefix(mut self, n: usize, prefix: &[K]) -> Option<Tree<K, V>> {
+        self.key.splice(..n, prefix.iter().cloned());
+        Tree::join(self.key, self.value, self.children)
+    }
+
     pub fn value(&self) -> Option<&V> {
         self.value.as_ref()
     }
@@ -44,71 +71,925 @@ impl<K: Eq + Clone, V> Tree<K, V> {
         &self.children
     }
 
+    /// Takes the node apart into its key, value and children.
+    pub fn into_parts(self) -> (Vec<K>, Option<V>, Vec<Tree<K, V>>) {
+        (self.key, self.value, self.children)
+    }
+
+    /// Borrows the key, the value and the children of the node at once.
+    pub fn parts_mut(&mut self) -> (&[K], Option<&mut V>, &mut [Tree<K, V>]) {
+        (&self.key, self.value.as_mut(), &mut self.children)
+    }
+
+    /// Returns the number of values in the subtree.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the child whose key starts with `first`.
+    pub fn child(&self, first: &K) -> Option<&Tree<K, V>> {
+        self.child_index(first).ok().map(|i| &self.children[i])
+    }
+
+    pub fn child_mut(&mut self, first: &K) -> Option<&mut Tree<K, V>> {
+        self.child_index(first)
+            .ok()
+            .map(move |i| &mut self.children[i])
+    }
+
     pub fn find(&self, key: &[K]) -> Option<&Tree<K, V>> {
-        let p = common_prefix(&self.key, key);
-        if p != self.key.len() {
+        if !key.starts_with(&self.key) {
+            return None;
+        }
+        self.find_below(&key[self.key.len()..])
+    }
+
+    pub fn find_mut(&mut self, key: &[K]) -> Option<&mut Tree<K, V>> {
+        if !key.starts_with(&self.key) {
+            return None;
+        }
+        let p = self.key.len();
+        self.find_below_mut(&key[p..])
+    }
+
+    /// Like `find`, but `key` is relative to the end of this node's key.
+    pub fn find_below(&self, key: &[K]) -> Option<&Tree<K, V>> {
+        match key.first() {
+            Some(first) => self.child(first)?.find(key),
+            None if self.value.is_some() => Some(self),
+            None => None,
+        }
+    }
+
+    /// Like `find_mut`, but `key` is relative to the end of this node's key.
+    pub fn find_below_mut(&mut self, key: &[K]) -> Option<&mut Tree<K, V>> {
+        match key.first() {
+            Some(first) => self.child_mut(first)?.find_mut(key),
+            None if self.value.is_some() => Some(self),
+            None => None,
+        }
+    }
+
+    /// Returns the topmost node whose subtree holds every key starting with
+    /// `prefix`, along with the length of the part of its key lying within
+    /// `prefix`.
+    pub fn find_prefix(&self, prefix: &[K]) -> Option<(&Tree<K, V>, usize)> {
+        let mut node = self;
+        let mut prefix = prefix;
+        loop {
+            let p = common_prefix(&node.key, prefix);
+            if p == prefix.len() {
+                return Some((node, p));
+            }
+            if p < node.key.len() {
+                return None;
+            }
+            prefix = &prefix[p..];
+            node = node.child(&prefix[0])?;
+        }
+    }
+
+    /// Like `find_prefix`, but returns all nodes on the way to the found one,
+    /// ending with it.
+    pub fn prefix_path(&self, prefix: &[K]) -> Option<(Vec<&Tree<K, V>>, usize)> {
+        let mut path = vec![];
+        let mut node = self;
+        let mut prefix = prefix;
+        loop {
+            let p = common_prefix(&node.key, prefix);
+            path.push(node);
+            if p == prefix.len() {
+                return Some((path, p));
+            }
+            if p < node.key.len() {
+                return None;
+            }
+            prefix = &prefix[p..];
+            node = node.child(&prefix[0])?;
+        }
+    }
+
+    /// Like `prefix_path`, but `prefix` is relative to the end of this node's key.
+    pub fn prefix_path_below(&self, prefix: &[K]) -> Option<(Vec<&Tree<K, V>>, usize)> {
+        match prefix.first() {
+            Some(first) => {
+                let (mut path, p) = self.child(first)?.prefix_path(prefix)?;
+                path.insert(0, self);
+                Some((path, p))
+            }
+            None => Some((vec![self], self.key.len())),
+        }
+    }
+
+    /// Like `find_prefix`, but returns only the last node.
+    pub fn find_prefix_mut(&mut self, prefix: &[K]) -> Option<(&mut Tree<K, V>, usize)> {
+        let mut node = self;
+        let mut prefix = prefix;
+        loop {
+            let p = common_prefix(&node.key, prefix);
+            if p == prefix.len() {
+                return Some((node, p));
+            }
+            if p < node.key.len() {
+                return None;
+            }
+            prefix = &prefix[p..];
+            node = node.child_mut(&prefix[0])?;
+        }
+    }
+
+    /// Returns the deepest node holding a value whose key is a prefix of `key`,
+    /// along with the length of that prefix.
+    pub fn longest_prefix(&self, key: &[K]) -> Option<(usize, &Tree<K, V>)> {
+        let mut found = None;
+        let mut node = self;
+        let mut depth = 0;
+        loop {
+            if !key[depth..].starts_with(&node.key) {
+                return found;
+            }
+            depth += node.key.len();
+            if node.value.is_some() {
+                found = Some((depth, node));
+            }
+            if depth == key.len() {
+                return found;
+            }
+            match node.child_index(&key[depth]) {
+                Ok(i) => node = &node.children[i],
+                Err(_) => return found,
+            }
+        }
+    }
+
+    /// Returns the number of values in the tree whose keys are less than `key`.
+    pub fn rank(&self, key: &[K]) -> usize {
+        let mut rank = 0;
+        let mut node = self;
+        let mut depth = 0;
+        loop {
+            let rest = &key[depth..];
+            let n = common_prefix(&node.key, rest);
+            if n < node.key.len() {
+                if n < rest.len() && node.key[n] < rest[n] {
+                    rank += node.count;
+                }
+                return rank;
+            }
+            depth += n;
+            let first = match key.get(depth) {
+                Some(first) => first,
+                None => return rank,
+            };
+            rank += node.value.is_some() as usize;
+            let i = match node.child_index(first) {
+                Ok(i) | Err(i) => i,
+            };
+            rank += node.children[..i].iter().map(|x| x.count).sum::<usize>();
+            match node.children.get(i) {
+                Some(child) if child.key[0] == *first => node = child,
+                _ => return rank,
+            }
+        }
+    }
+
+    /// Returns the key and the value of the `index`-th value in the tree, in
+    /// lexicographic order.
+    pub fn select(&self, mut index: usize) -> Option<(Vec<K>, &V)> {
+        if index >= self.count {
+            return None;
+        }
+        let mut key = vec![];
+        let mut node = self;
+        'descend: loop {
+            key.extend_from_slice(&node.key);
+            if let Some(value) = &node.value {
+                if index == 0 {
+                    return Some((key, value));
+                }
+                index -= 1;
+            }
+            for child in &node.children {
+                if index < child.count {
+                    node = child;
+                    continue 'descend;
+                }
+                index -= child.count;
+            }
+            return None;
+        }
+    }
+
+    /// Returns the path to the node holding the first value in lexicographic
+    /// order, or the last one if `back` is set, along with its key.
+    pub fn end_path(&self, back: bool) -> Option<(Vec<usize>, Vec<K>)> {
+        if self.count == 0 {
             return None;
         }
-        if p < key.len() {
-            self.children
-                .iter()
-                .map(|x| x.find(&key[p..]))
-                .filter_map(|x| x)
-                .next()
-        } else if self.value.is_some() {
-            Some(self)
+        let mut path = vec![];
+        let mut key = vec![];
+        let mut node = self;
+        loop {
+            key.extend_from_slice(&node.key);
+            // Every leaf holds a value, and a node comes before its children.
+            let i = match node.children.len() {
+                0 => return Some((path, key)),
+                _ if !back && node.value.is_some() => return Some((path, key)),
+                len if back => len - 1,
+                _ => 0,
+            };
+            path.push(i);
+            node = &node.children[i];
+        }
+    }
+
+    /// Returns the path to the node holding `key`, or else the path to the
+    /// deepest node `key` reaches along with the position in `key` at which
+    /// the key of that node starts.
+    pub fn locate(&self, key: &[K]) -> Result<Vec<usize>, (Vec<usize>, usize)> {
+        let mut path = vec![];
+        let mut node = self;
+        let mut start = 0;
+        loop {
+            let p = common_prefix(&node.key, &key[start..]);
+            if p != node.key.len() {
+                return Err((path, start));
+            }
+            let rest = &key[start + p..];
+            if rest.is_empty() {
+                return match node.value {
+                    Some(_) => Ok(path),
+                    None => Err((path, start)),
+                };
+            }
+            match node.child_index(&rest[0]) {
+                Ok(i) => {
+                    path.push(i);
+                    node = &node.children[i];
+                    start += p;
+                }
+                Err(_) => return Err((path, start)),
+            }
+        }
+    }
+
+    pub fn at_path(&self, path: &[usize]) -> &Tree<K, V> {
+        path.iter().fold(self, |node, &i| &node.children[i])
+    }
+
+    pub fn at_path_mut(&mut self, path: &[usize]) -> &mut Tree<K, V> {
+        path.iter().fold(self, |node, &i| &mut node.children[i])
+    }
+
+    pub fn insert(&mut self, key: &[K], value: V) -> Option<V> {
+        let p = common_prefix(&self.key, key);
+        if p < self.key.len() {
+            self.split(p);
+        }
+        let old = if p == key.len() {
+            self.value.replace(value)
         } else {
-            None
+            match self.child_index(&key[p]) {
+                Ok(i) => self.children[i].insert(&key[p..], value),
+                Err(i) => {
+                    self.children.insert(i, Tree::new(key[p..].to_vec(), value));
+                    None
+                }
+            }
+        };
+        if old.is_none() {
+            self.count += 1;
         }
+        old
     }
 
-    pub fn find_mut(&mut self, key: &[K]) -> Option<&mut Tree<K, V>> {
+    /// Inserts a value for `key` into the node at the end of `path`, where
+    /// `key` is relative to the start of that node's key.
+    pub fn insert_at(&mut self, path: &[usize], key: &[K], value: V) -> Option<V> {
+        let old = match path.split_first() {
+            None => return self.insert(key, value),
+            Some((&i, rest)) => self.children[i].insert_at(rest, key, value),
+        };
+        if old.is_none() {
+            self.count += 1;
+        }
+        old
+    }
+
+    /// Inserts a value for `key`, which must not hold one yet, and returns
+    /// a reference to it.
+    pub fn insert_vacant(&mut self, key: &[K], value: V) -> &mut V {
+        let p = common_prefix(&self.key, key);
+        if p < self.key.len() {
+            self.split(p);
+        }
+        self.count += 1;
+        if p == key.len() {
+            debug_assert!(self.value.is_none());
+            return self.value.get_or_insert(value);
+        }
+        let i = match self.child_index(&key[p]) {
+            Ok(i) => return self.children[i].insert_vacant(&key[p..], value),
+            Err(i) => i,
+        };
+        self.children.insert(i, Tree::new(key[p..].to_vec(), value));
+        self.children[i].value.as_mut().unwrap()
+    }
+
+    /// Like [`insert_vacant`](#method.insert_vacant), but for the node at
+    /// the end of `path`, where `key` is relative to the start of that node's
+    /// key.
+    pub fn insert_vacant_at(&mut self, path: &[usize], key: &[K], value: V) -> &mut V {
+        match path.split_first() {
+            None => self.insert_vacant(key, value),
+            Some((&i, rest)) => {
+                self.count += 1;
+                self.children[i].insert_vacant_at(rest, key, value)
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &[K]) -> Option<V> {
         let p = common_prefix(&self.key, key);
         if p != self.key.len() {
             return None;
         }
-        if p < key.len() {
-            self.children
-                .iter_mut()
-                .map(|x| x.find_mut(&key[p..]))
-                .filter_map(|x| x)
-                .next()
-        } else if self.value.is_some() {
-            Some(self)
+        let value = if p == key.len() {
+            self.value.take()
         } else {
-            None
+            let i = self.child_index(&key[p]).ok()?;
+            let value = self.children[i].remove(&key[p..]);
+            if value.is_some() {
+                self.compact_child(i);
+            }
+            value
+        };
+        if value.is_some() {
+            self.count -= 1;
         }
+        value
     }
 
-    pub fn insert(&mut self, key: &[K], value: V) -> Option<V> {
-        let p = common_prefix(&self.key, key);
+    /// Detaches the subtree holding every key starting with `prefix`, which is
+    /// relative to the end of this node's key. The key of the detached node is
+    /// extended to be relative to the same position.
+    pub fn remove_prefix_below(&mut self, prefix: &[K]) -> Option<Tree<K, V>> {
+        let first = match prefix.first() {
+            Some(first) => first,
+            None => return Some(mem::replace(self, Tree::empty())),
+        };
+        let i = self.child_index(first).ok()?;
+        let child = &mut self.children[i];
+        let p = common_prefix(&child.key, prefix);
+        let subtree = if p == prefix.len() {
+            self.children.remove(i)
+        } else if p == child.key.len() {
+            let subtree = child.remove_prefix_below(&prefix[p..])?;
+            let mut key = child.key.clone();
+            key.extend(subtree.key);
+            self.compact_child(i);
+            Tree { key, ..subtree }
+        } else {
+            return None;
+        };
+        self.count -= subtree.count;
+        Some(subtree)
+    }
+
+    /// Detaches the node at the end of `path`. The key of the detached node is
+    /// extended to be relative to the start of this node's key.
+    pub fn remove_subtree_at(&mut self, path: &[usize]) -> Tree<K, V> {
+        let subtree = match path.split_first() {
+            None => return mem::replace(self, Tree::empty()),
+            Some((&i, [])) => self.children.remove(i),
+            Some((&i, rest)) => {
+                let subtree = self.children[i].remove_subtree_at(rest);
+                let mut key = self.children[i].key.clone();
+                key.extend(subtree.key);
+                self.compact_child(i);
+                Tree { key, ..subtree }
+            }
+        };
+        self.count -= subtree.count;
+        subtree
+    }
+
+    /// Removes the value of the node at the end of `path`.
+    pub fn remove_at(&mut self, path: &[usize]) -> Option<V> {
+        let value = match path.split_first() {
+            None => self.value.take(),
+            Some((&i, rest)) => {
+                let value = self.children[i].remove_at(rest);
+                if value.is_some() {
+                    self.compact_child(i);
+                }
+                value
+            }
+        };
+        if value.is_some() {
+            self.count -= 1;
+        }
+        value
+    }
+
+    /// Calls `f` on every value in lexicographic order, along with its key.
+    /// `key` holds the keys of the ancestors and is left unchanged.
+    pub fn for_each<'a, F>(&'a self, key: &mut Vec<K>, f: &mut F)
+    where
+        F: FnMut(&[K], &'a V),
+    {
+        key.extend_from_slice(&self.key);
+        if let Some(value) = &self.value {
+            f(key, value);
+        }
+        for child in &self.children {
+            child.for_each(key, f);
+        }
+        key.truncate(key.len() - self.key.len());
+    }
+
+    /// Removes the values for which `f` returns `false`, visiting them in
+    /// lexicographic order. `key` holds the keys of the ancestors and is left
+    /// unchanged.
+    pub fn retain<F>(&mut self, key: &mut Vec<K>, f: &mut F)
+    where
+        F: FnMut(&[K], &mut V) -> bool,
+    {
+        key.extend_from_slice(&self.key);
+        if let Some(value) = &mut self.value {
+            if !f(key, value) {
+                self.value = None;
+            }
+        }
+        let mut i = 0;
+        while i < self.children.len() {
+            self.children[i].retain(key, f);
+            let len = self.children.len();
+            self.compact_child(i);
+            if self.children.len() == len {
+                i += 1;
+            }
+        }
+        key.truncate(key.len() - self.key.len());
+        self.count = self.value.is_some() as usize;
+        self.count += self.children.iter().map(|x| x.count).sum::<usize>();
+    }
+
+    /// Moves the keys of `other`, whose key is relative to the start of this
+    /// node's key, into this tree. Subtrees which don't overlap are moved as
+    /// a whole, and `f` resolves the values of keys found in both trees.
+    /// `key` holds the keys of the ancestors and is left unchanged.
+    pub fn merge<F>(&mut self, mut other: Tree<K, V>, key: &mut Vec<K>, f: &mut F)
+    where
+        F: FnMut(&[K], V, V) -> V,
+    {
+        let p = common_prefix(&self.key, &other.key);
         if p < self.key.len() {
-            let child = Tree {
-                key: self.key.split_off(p),
-                value: self.value.take(),
-                children: mem::take(&mut self.children),
+            self.split(p);
+        }
+        if p < other.key.len() {
+            other.split(p);
+        }
+        key.extend_from_slice(&self.key);
+        self.value = match (self.value.take(), other.value) {
+            (Some(a), Some(b)) => Some(f(key, a, b)),
+            (a, b) => a.or(b),
+        };
+        for child in other.children {
+            match self.child_index(&child.key[0]) {
+                Ok(i) => self.children[i].merge(child, key, f),
+                Err(i) => self.children.insert(i, child),
+            }
+        }
+        key.truncate(key.len() - self.key.len());
+        self.count = self.value.is_some() as usize;
+        self.count += self.children.iter().map(|x| x.count).sum::<usize>();
+    }
+
+    /// Detaches the keys which are greater than or equal to `key`, which is
+    /// relative to the end of this node's key. The detached node has the same
+    /// key as this one.
+    pub fn split_off_below(&mut self, key: &[K]) -> Tree<K, V> {
+        let first = match key.first() {
+            Some(first) => first,
+            None => {
+                let key = self.key.clone();
+                return mem::replace(
+                    self,
+                    Tree {
+                        key,
+                        ..Tree::empty()
+                    },
+                );
+            }
+        };
+        let i = match self.child_index(first) {
+            Ok(i) | Err(i) => i,
+        };
+        let mut right = self.children.split_off(i);
+        if right.first().is_some_and(|x| x.key[0] == *first) {
+            let mut child = right.remove(0);
+            let p = common_prefix(&child.key, key);
+            if p == key.len() || p < child.key.len() && child.key[p] > key[p] {
+                right.insert(0, child);
+            } else if p < child.key.len() {
+                self.children.push(child);
+            } else {
+                let part = child.split_off_below(&key[p..]);
+                self.children.push(child);
+                self.compact_child(i);
+                right.splice(0..0, Tree::join(part.key, part.value, part.children));
+            }
+        }
+        let count = right.iter().map(|x| x.count).sum();
+        self.count -= count;
+        Tree {
+            key: self.key.clone(),
+            value: None,
+            children: right,
+            count,
+        }
+    }
+
+    /// Moves the part of the key past `at`, along with the value and
+    /// children, to a new child.
+    fn split(&mut self, at: usize) {
+        let child = Tree {
+            key: self.key.split_off(at),
+            value: self.value.take(),
+            children: mem::take(&mut self.children),
+            count: self.count,
+        };
+        self.children.push(child);
+    }
+
+    /// Searches the children, which are kept sorted by the first element
+    /// of their keys, for the one starting with `first`.
+    pub fn child_index(&self, first: &K) -> Result<usize, usize> {
+        self.children.binary_search_by(|x| x.key[0].cmp(first))
+    }
+
+    /// Creates a compact node from its parts, merging it with its only child
+    /// if it holds no value.
+    fn join(
+        mut key: Vec<K>,
+        value: Option<V>,
+        mut children: Vec<Tree<K, V>>,
+    ) -> Option<Tree<K, V>> {
+        if value.is_none() {
+            match children.len() {
+                0 => return None,
+                1 => {
+                    let child = children.pop().unwrap();
+                    key.extend(child.key);
+                    return Some(Tree { key, ..child });
+                }
+                _ => {}
+            }
+        }
+        let count = value.is_some() as usize + children.iter().map(|x| x.count).sum::<usize>();
+        Some(Tree {
+            key,
+            value,
+            children,
+            count,
+        })
+    }
+
+    /// Prunes the child at `index` if it holds neither a value nor children,
+    /// or merges it with its only child if it holds no value.
+    fn compact_child(&mut self, index: usize) {
+        let child = &mut self.children[index];
+        if child.value.is_some() {
+            return;
+        }
+        match child.children.len() {
+            0 => {
+                self.children.remove(index);
+            }
+            1 => {
+                let grandchild = child.children.pop().unwrap();
+                child.key.extend(grandchild.key);
+                child.value = grandchild.value;
+                child.children = grandchild.children;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Which keys to keep when combining two trees, depending on whether they
+/// are in the left tree, the right tree, or both.
+#[derive(Clone, Copy, Debug)]
+pub enum SetOp {
+    Union,
+    Intersection,
+    Difference,
+    SymmetricDifference,
+}
+
+impl SetOp {
+    fn keeps(self, left: bool, right: bool) -> bool {
+        match self {
+            SetOp::Union => left || right,
+            SetOp::Intersection => left && right,
+            SetOp::Difference => left && !right,
+            SetOp::SymmetricDifference => left != right,
+        }
+    }
+}
+
+/// The keys of a subtree with the first `offset` elements of its key cut
+/// off.
+struct Suffix<'a, K: 'a, V: 'a> {
+    tree: &'a Tree<K, V>,
+    offset: usize,
+}
+
+type SuffixPair<'a, K, V> = (Option<Suffix<'a, K, V>>, Option<Suffix<'a, K, V>>);
+
+/// Sibling subtrees, sorted by their keys, with the first `offset` elements
+/// of each key cut off.
+struct Siblings<'a, K: 'a, V: 'a> {
+    trees: &'a [Tree<K, V>],
+    offset: usize,
+}
+
+impl<'a, K: 'a, V: 'a> Siblings<'a, K, V> {
+    fn first(&self) -> Option<&'a K> {
+        self.trees.first().map(|x| &x.key[self.offset])
+    }
+
+    fn pop_first(&mut self) -> Option<Suffix<'a, K, V>> {
+        let (tree, rest) = self.trees.split_first()?;
+        self.trees = rest;
+        Some(Suffix {
+            tree,
+            offset: self.offset,
+        })
+    }
+}
+
+/// Suffixes of two runs of siblings, paired up by their first element.
+struct Pairs<'a, K: 'a, V: 'a> {
+    a: Siblings<'a, K, V>,
+    b: Siblings<'a, K, V>,
+}
+
+impl<'a, K: 'a + Ord, V: 'a> Iterator for Pairs<'a, K, V> {
+    type Item = SuffixPair<'a, K, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let order = match (self.a.first(), self.b.first()) {
+            (Some(x), Some(y)) => x.cmp(y),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => return None,
+        };
+        Some(match order {
+            Ordering::Less => (self.a.pop_first(), None),
+            Ordering::Greater => (None, self.b.pop_first()),
+            Ordering::Equal => (self.a.pop_first(), self.b.pop_first()),
+        })
+    }
+}
+
+/// Two suffixes split at the end of the common prefix of their keys.
+struct Aligned<'a, K: 'a, V: 'a> {
+    /// Length of the common prefix.
+    len: usize,
+    /// Values found right after the common prefix, in either suffix.
+    values: (Option<&'a V>, Option<&'a V>),
+    /// Suffixes following the common prefix, paired up by their first
+    /// element.
+    pairs: Pairs<'a, K, V>,
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> Suffix<'a, K, V> {
+    fn key(&self) -> &'a [K] {
+        &self.tree.key[self.offset..]
+    }
+
+    fn align(a: Suffix<'a, K, V>, b: Suffix<'a, K, V>) -> Aligned<'a, K, V> {
+        let len = common_prefix(a.key(), b.key());
+        let (a_value, a) = a.advance(len);
+        let (b_value, b) = b.advance(len);
+        Aligned {
+            len,
+            values: (a_value, b_value),
+            pairs: Pairs { a, b },
+        }
+    }
+
+    /// Cuts off `n` elements of the key, returning the value found there and
+    /// the suffixes which follow.
+    fn advance(self, n: usize) -> (Option<&'a V>, Siblings<'a, K, V>) {
+        if n < self.key().len() {
+            let rest = Siblings {
+                trees: slice::from_ref(self.tree),
+                offset: self.offset + n,
             };
-            self.children.push(child);
+            return (None, rest);
         }
-        if p == key.len() {
-            self.value.replace(value)
+        let rest = Siblings {
+            trees: &self.tree.children,
+            offset: 0,
+        };
+        (self.tree.value.as_ref(), rest)
+    }
+
+    /// Returns `true` if `combine` would keep any key, stopping at the first
+    /// one found.
+    fn combines_any(a: Suffix<'a, K, V>, b: Suffix<'a, K, V>, op: SetOp) -> bool {
+        let Aligned {
+            values, mut pairs, ..
+        } = Suffix::align(a, b);
+        op.keeps(values.0.is_some(), values.1.is_some())
+            || pairs.any(|pair| match pair {
+                (Some(a), Some(b)) => Suffix::combines_any(a, b, op),
+                (Some(_), None) => op.keeps(true, false),
+                (None, Some(_)) => op.keeps(false, true),
+                (None, None) => false,
+            })
+    }
+}
+
+impl<K: Ord + Clone, V> Tree<K, V> {
+    /// Returns `true` if `combine` would keep any key, without building the
+    /// tree.
+    pub fn combines_any(&self, other: &Tree<K, V>, op: SetOp) -> bool {
+        let a = Suffix {
+            tree: self,
+            offset: 0,
+        };
+        let b = Suffix {
+            tree: other,
+            offset: 0,
+        };
+        Suffix::combines_any(a, b, op)
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a + Clone> Suffix<'a, K, V> {
+    fn to_tree(&self) -> Tree<K, V> {
+        Tree {
+            key: self.key().to_vec(),
+            ..self.tree.clone()
+        }
+    }
+
+    fn combine(a: Suffix<'a, K, V>, b: Suffix<'a, K, V>, op: SetOp) -> Option<Tree<K, V>> {
+        let key = a.key();
+        let Aligned { len, values, pairs } = Suffix::align(a, b);
+        let value = if op.keeps(values.0.is_some(), values.1.is_some()) {
+            values.0.or(values.1).cloned()
         } else {
-            let mut child = self
-                .children
-                .iter_mut()
-                .find(|x| common_prefix(&x.key, &key[p..]) > 0);
-            if let Some(ref mut child) = child {
-                child.insert(&key[p..], value)
-            } else {
-                self.children.push(Tree::new(key[p..].to_vec(), value));
-                None
+            None
+        };
+        let children = pairs
+            .filter_map(|pair| match pair {
+                (Some(a), Some(b)) => Suffix::combine(a, b, op),
+                (Some(a), None) if op.keeps(true, false) => Some(a.to_tree()),
+                (None, Some(b)) if op.keeps(false, true) => Some(b.to_tree()),
+                _ => None,
+            })
+            .collect();
+        Tree::join(key[..len].to_vec(), value, children)
+    }
+}
+
+impl<K: Ord + Clone, V: PartialEq> Tree<K, V> {
+    /// Gets an iterator over every key whose value differs between this tree
+    /// and `other`, along with both values, in lexicographic order.
+    pub fn diff<'a>(&'a self, other: &'a Tree<K, V>) -> DiffIter<'a, K, V> {
+        let a = Suffix {
+            tree: self,
+            offset: 0,
+        };
+        let b = Suffix {
+            tree: other,
+            offset: 0,
+        };
+        DiffIter {
+            key: vec![],
+            next: Some((Some(a), Some(b))),
+            stack: vec![],
+        }
+    }
+}
+
+/// Walks two trees in lockstep, aligning the suffixes found in both and
+/// walking those found in only one of them without further comparisons.
+/// Suffixes of the very same subtree are skipped.
+pub struct DiffIter<'a, K: 'a, V: 'a> {
+    key: Vec<K>,
+    /// The pair of suffixes to visit before resuming the stack.
+    next: Option<SuffixPair<'a, K, V>>,
+    /// Pairs of suffixes left to visit, along with the length of the key
+    /// before them.
+    stack: Vec<(usize, Pairs<'a, K, V>)>,
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a> DiffIter<'a, K, V> {
+    /// Returns the next pair of suffixes to visit, with `key` truncated to
+    /// the keys before them.
+    fn next_pair(&mut self) -> Option<SuffixPair<'a, K, V>> {
+        if let Some(pair) = self.next.take() {
+            return Some(pair);
+        }
+        loop {
+            let (depth, pairs) = self.stack.last_mut()?;
+            if let Some(pair) = pairs.next() {
+                self.key.truncate(*depth);
+                return Some(pair);
             }
+            self.stack.pop();
         }
     }
 
-    pub fn remove(&mut self, key: &[K]) -> Option<V> {
-        // TODO: squash keys
-        self.find_mut(key).and_then(|x| x.value.take())
+    /// Enters a suffix found in only one of the trees, returning its value.
+    fn descend(&mut self, suffix: Suffix<'a, K, V>, left: bool) -> Option<&'a V> {
+        let key = suffix.key();
+        self.key.extend_from_slice(key);
+        let (value, rest) = suffix.advance(key.len());
+        let none = Siblings {
+            trees: &[],
+            offset: 0,
+        };
+        let pairs = if left {
+            Pairs { a: rest, b: none }
+        } else {
+            Pairs { a: none, b: rest }
+        };
+        self.stack.push((self.key.len(), pairs));
+        value
+    }
+}
+
+impl<'a, K: 'a + Ord + Clone, V: 'a + PartialEq> Iterator for DiffIter<'a, K, V> {
+    type Item = (Vec<K>, Option<&'a V>, Option<&'a V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let values = match self.next_pair()? {
+                (Some(a), Some(b)) => {
+                    if ptr::eq(a.tree, b.tree) && a.offset == b.offset {
+                        continue;
+                    }
+                    let prefix = a.key();
+                    let Aligned { len, values, pairs } = Suffix::align(a, b);
+                    self.key.extend_from_slice(&prefix[..len]);
+                    self.stack.push((self.key.len(), pairs));
+                    values
+                }
+                (Some(a), None) => (self.descend(a, true), None),
+                (None, Some(b)) => (None, self.descend(b, false)),
+                (None, None) => unreachable!(),
+            };
+            if values.0 != values.1 {
+                return Some((self.key.clone(), values.0, values.1));
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let count = |x: &Siblings<'a, K, V>| x.trees.iter().map(|x| x.count).sum::<usize>();
+        let suffix = |x: &Option<Suffix<'a, K, V>>| x.as_ref().map_or(0, |x| x.tree.count);
+        let next = self.next.as_ref().map_or(0, |(a, b)| suffix(a) + suffix(b));
+        let upper = self.stack.iter().map(|(_, x)| count(&x.a) + count(&x.b));
+        (0, Some(next + upper.sum::<usize>()))
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Tree<K, V> {
+    /// Builds a new root holding the keys of this tree and `other` which are
+    /// kept by `op`, taking values from this tree first. Subtrees found in
+    /// only one of the trees are copied or skipped as a whole.
+    pub fn combine(&self, other: &Tree<K, V>, op: SetOp) -> Tree<K, V> {
+        let a = Suffix {
+            tree: self,
+            offset: 0,
+        };
+        let b = Suffix {
+            tree: other,
+            offset: 0,
+        };
+        Suffix::combine(a, b, op).map_or_else(Tree::empty, Tree::from_subtree)
+    }
+}
+
+#[cfg(test)]
+impl<K: Ord + Clone, V> Tree<K, V> {
+    /// Checks that the children of every node are sorted, that every node
+    /// below this one has a non-empty key and either a value or two children,
+    /// and that the counts are right.
+    pub fn is_compact(&self) -> bool {
+        self.count
+            == self.value.iter().count() + self.children.iter().map(|x| x.count).sum::<usize>()
+            && self.children.windows(2).all(|x| x[0].key[0] < x[1].key[0])
+            && self.children.iter().all(|x| {
+                !x.key.is_empty() && (x.value.is_some() || x.children.len() > 1) && x.is_compact()
+            })
     }
 }
 
@@ -130,10 +1011,12 @@ mod tests {
                 Tree {
                     key: vec![1, 2],
                     value: Some(0),
-                    children: vec![Tree::new(vec![3], 1), Tree::new(vec![-3], 2)],
+                    children: vec![Tree::new(vec![-3], 2), Tree::new(vec![3], 1)],
+                    count: 3,
                 },
                 Tree::new(vec![9, 8, 7], 3),
             ],
+            count: 4,
         }
     }
 
@@ -193,15 +1076,391 @@ mod tests {
         assert_eq!(root.find(&[1, 2, 5, 6]).and_then(|x| x.value), Some(9));
     }
 
+    #[test]
+    fn test_insert_keeps_children_sorted() {
+        let mut root = Tree::empty();
+        root.insert(&[5, 1], 0);
+        root.insert(&[2], 1);
+        root.insert(&[9], 2);
+        root.insert(&[5, 0], 3);
+        root.insert(&[0, 4], 4);
+
+        let firsts = root
+            .children()
+            .iter()
+            .map(|x| x.key()[0])
+            .collect::<Vec<_>>();
+        assert_eq!(firsts, vec![0, 2, 5, 9]);
+        let firsts = root.children()[2]
+            .children()
+            .iter()
+            .map(|x| x.key()[0])
+            .collect::<Vec<_>>();
+        assert_eq!(firsts, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_find_prefix() {
+        let t = sample_tree();
+        let find = |prefix: &[i32]| {
+            t.prefix_path(prefix)
+                .map(|(path, p)| (path.iter().map(|x| x.key().to_vec()).collect(), p))
+        };
+        assert_eq!(find(&[]), Some((vec![vec![]], 0)));
+        assert_eq!(find(&[1]), Some((vec![vec![], vec![1, 2]], 1)));
+        assert_eq!(find(&[1, 2]), Some((vec![vec![], vec![1, 2]], 2)));
+        assert_eq!(
+            find(&[1, 2, 3]),
+            Some((vec![vec![], vec![1, 2], vec![3]], 1))
+        );
+        assert_eq!(find(&[9, 8]), Some((vec![vec![], vec![9, 8, 7]], 2)));
+        assert_eq!(find(&[1, 3]), None);
+        assert_eq!(find(&[1, 2, 3, 4]), None);
+        assert_eq!(find(&[9, 8, 7, 6]), None);
+    }
+
+    #[test]
+    fn test_count() {
+        let mut t = sample_tree();
+        assert_eq!(t.count(), 4);
+        t.remove(&[1, 2]);
+        assert_eq!(t.count(), 3);
+        assert_eq!(t.children()[0].count(), 2);
+    }
+
+    #[test]
+    fn test_longest_prefix() {
+        let t = sample_tree();
+        let longest = |key: &[i32]| t.longest_prefix(key).map(|(n, x)| (n, x.value));
+        assert_eq!(longest(&[1, 2, 3, 4]), Some((3, Some(1))));
+        assert_eq!(longest(&[1, 2, 3]), Some((3, Some(1))));
+        assert_eq!(longest(&[1, 2, 4]), Some((2, Some(0))));
+        assert_eq!(longest(&[1, 2]), Some((2, Some(0))));
+        assert_eq!(longest(&[1]), None);
+        assert_eq!(longest(&[9, 8]), None);
+        assert_eq!(longest(&[]), None);
+    }
+
+    #[test]
+    fn test_rank() {
+        let t = sample_tree();
+        assert_eq!(t.rank(&[]), 0);
+        assert_eq!(t.rank(&[1]), 0);
+        assert_eq!(t.rank(&[1, 2]), 0);
+        assert_eq!(t.rank(&[1, 2, -4]), 1);
+        assert_eq!(t.rank(&[1, 2, 0]), 2);
+        assert_eq!(t.rank(&[1, 2, 3]), 2);
+        assert_eq!(t.rank(&[1, 2, 3, 0]), 3);
+        assert_eq!(t.rank(&[1, 3]), 3);
+        assert_eq!(t.rank(&[9, 8]), 3);
+        assert_eq!(t.rank(&[9, 8, 7]), 3);
+        assert_eq!(t.rank(&[10]), 4);
+    }
+
+    #[test]
+    fn test_select() {
+        let t = sample_tree();
+        assert_eq!(t.select(0), Some((vec![1, 2], &0)));
+        assert_eq!(t.select(1), Some((vec![1, 2, -3], &2)));
+        assert_eq!(t.select(2), Some((vec![1, 2, 3], &1)));
+        assert_eq!(t.select(3), Some((vec![9, 8, 7], &3)));
+        assert_eq!(t.select(4), None);
+    }
+
+    #[test]
+    fn test_insert_vacant() {
+        let mut root = sample_tree();
+        *root.insert_vacant(&[1], 5) += 1;
+        *root.insert_vacant(&[9, 9], 7) += 1;
+        *root.insert_vacant(&[], 9) += 1;
+        assert_eq!(root.find(&[1]).and_then(|x| x.value), Some(6));
+        assert_eq!(root.find(&[9, 9]).and_then(|x| x.value), Some(8));
+        assert_eq!(root.find(&[9, 8, 7]).and_then(|x| x.value), Some(3));
+        assert_eq!(root.find(&[]).and_then(|x| x.value), Some(10));
+    }
+
+    #[test]
+    fn test_locate() {
+        let t = sample_tree();
+        assert_eq!(t.locate(&[1, 2]), Ok(vec![0]));
+        assert_eq!(t.locate(&[1, 2, 3]), Ok(vec![0, 1]));
+        assert_eq!(t.locate(&[9, 8, 7]), Ok(vec![1]));
+        assert_eq!(t.locate(&[]), Err((vec![], 0)));
+        assert_eq!(t.locate(&[1]), Err((vec![0], 0)));
+        assert_eq!(t.locate(&[1, 2, 4]), Err((vec![0], 0)));
+        assert_eq!(t.locate(&[1, 2, 3, 4]), Err((vec![0, 1], 2)));
+        assert_eq!(t.locate(&[9, 8]), Err((vec![1], 0)));
+        assert_eq!(t.locate(&[5]), Err((vec![], 0)));
+        assert_eq!(t.at_path(&[0, 1]).value, Some(1));
+    }
+
+    #[test]
+    fn test_end_path() {
+        let t = sample_tree();
+        assert_eq!(t.end_path(false), Some((vec![0], vec![1, 2])));
+        assert_eq!(t.end_path(true), Some((vec![1], vec![9, 8, 7])));
+        assert_eq!(Tree::<i32, u8>::empty().end_path(false), None);
+        assert_eq!(Tree::<i32, u8>::new(vec![], 1).end_path(true), Some((vec![], vec![])));
+    }
+
+    #[test]
+    fn test_insert_vacant_at() {
+        let mut root = sample_tree();
+        *root.insert_vacant_at(&[0, 1], &[3, 4], 4) += 1;
+        *root.insert_vacant_at(&[1], &[9, 8], 5) += 1;
+        *root.insert_vacant_at(&[], &[], 6) += 1;
+        assert_eq!(root.find(&[1, 2, 3, 4]).and_then(|x| x.value), Some(5));
+        assert_eq!(root.find(&[9, 8]).and_then(|x| x.value), Some(6));
+        assert_eq!(root.find(&[]).and_then(|x| x.value), Some(7));
+        assert_eq!(root.children()[0].count(), 4);
+        assert_eq!(root.count(), 7);
+        assert!(root.is_compact());
+    }
+
+    #[test]
+    fn test_remove_at() {
+        let mut root = sample_tree();
+        assert_eq!(root.remove_at(&[0]), Some(0));
+        assert_eq!(root.remove_at(&[0, 0]), Some(2));
+        assert_eq!(root.children()[0].key(), &[1, 2, 3]);
+        assert_eq!(root.children()[0].value(), Some(&1));
+    }
+
+    #[test]
+    fn test_insert_at() {
+        let mut root = sample_tree();
+        assert_eq!(root.insert_at(&[0, 1], &[3, 4], 4), None);
+        assert_eq!(root.insert_at(&[0], &[1, 2], 5), Some(0));
+        assert_eq!(root.find(&[1, 2, 3, 4]).and_then(|x| x.value), Some(4));
+        assert_eq!(root.count(), 5);
+        assert!(root.is_compact());
+    }
+
+    #[test]
+    fn test_remove_subtree_at() {
+        let mut root = sample_tree();
+        let subtree = root.remove_subtree_at(&[0, 0]);
+        assert_eq!(subtree.key(), &[1, 2, -3]);
+        assert_eq!(subtree.value(), Some(&2));
+        assert_eq!(root.count(), 3);
+        assert!(root.is_compact());
+
+        let subtree = root.remove_subtree_at(&[0]);
+        assert_eq!(subtree.key(), &[1, 2]);
+        assert_eq!(subtree.count(), 2);
+        assert_eq!(root.count(), 1);
+        assert!(root.is_compact());
+    }
+
+    #[test]
+    fn test_remove_prefix_below() {
+        let mut root = sample_tree();
+        let subtree = root.remove_prefix_below(&[1, 2, 3]).unwrap();
+        assert_eq!(subtree.key(), &[1, 2, 3]);
+        assert_eq!(subtree.value(), Some(&1));
+        assert_eq!(root.children()[0].key(), &[1, 2]);
+        assert_eq!(root.children()[0].children().len(), 1);
+
+        let subtree = root.remove_prefix_below(&[1]).unwrap();
+        assert_eq!(subtree.key(), &[1, 2]);
+        assert_eq!(subtree.count(), 2);
+        assert_eq!(root.children().len(), 1);
+
+        assert!(root.remove_prefix_below(&[9, 7]).is_none());
+        assert!(root.remove_prefix_below(&[9, 8, 7, 6]).is_none());
+        let all = root.remove_prefix_below(&[]).unwrap();
+        assert_eq!(all.count(), 1);
+        assert_eq!(root.count(), 0);
+    }
+
+    #[test]
+    fn test_remove_prefix_below_compacts() {
+        let mut root = Tree::empty();
+        root.insert(&[1, 2, 3], 0);
+        root.insert(&[1, 2, 4], 1);
+        root.insert(&[1, 5], 2);
+        let subtree = root.remove_prefix_below(&[1, 2]).unwrap();
+        assert_eq!(subtree.key(), &[1, 2]);
+        assert_eq!(root.children().len(), 1);
+        assert_eq!(root.children()[0].key(), &[1, 5]);
+        assert!(root.children()[0].children().is_empty());
+    }
+
     #[test]
     fn test_remove() {
         let mut root = sample_tree();
 
-        assert_eq!(root.find(&[9, 8, 7]).is_some(), true);
+        assert!(root.find(&[9, 8, 7]).is_some());
         root.remove(&[9, 8, 7]);
         root.remove(&[1, 2]);
-        assert_eq!(root.find(&[9, 8, 7]).is_some(), false);
-        assert_eq!(root.find(&[1, 2]).is_some(), false);
+        assert!(root.find(&[9, 8, 7]).is_none());
+        assert!(root.find(&[1, 2]).is_none());
         assert_eq!(root.find(&[1, 2, 3]).and_then(|x| x.value), Some(1));
+        assert_eq!(root.remove(&[1, 2]), None);
+        assert_eq!(root.remove(&[1]), None);
+        assert_eq!(root.remove(&[1, 2, 3]), Some(1));
+    }
+
+    #[test]
+    fn test_remove_compacts() {
+        let mut root = sample_tree();
+
+        root.remove(&[9, 8, 7]);
+        assert_eq!(root.children().len(), 1);
+
+        root.remove(&[1, 2]);
+        root.remove(&[1, 2, 3]);
+        assert_eq!(root.children().len(), 1);
+        assert_eq!(root.children()[0].key(), &[1, 2, -3]);
+        assert_eq!(root.children()[0].value(), Some(&2));
+        assert!(root.children()[0].children().is_empty());
+
+        root.remove(&[1, 2, -3]);
+        assert!(root.children().is_empty());
+    }
+
+    #[test]
+    fn test_for_each() {
+        let mut entries = vec![];
+        let mut key = vec![0];
+        sample_tree().for_each(&mut key, &mut |k, &v| entries.push((k.to_vec(), v)));
+        assert_eq!(key, vec![0]);
+        assert_eq!(
+            entries,
+            vec![
+                (vec![0, 1, 2], 0),
+                (vec![0, 1, 2, -3], 2),
+                (vec![0, 1, 2, 3], 1),
+                (vec![0, 9, 8, 7], 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_combine() {
+        let a = sample_tree();
+        let mut b = Tree::empty();
+        b.insert(&[1, 2, 3], 4);
+        b.insert(&[1, 2, 5], 5);
+        b.insert(&[9], 6);
+        let keys = |t: &Tree<i32, u8>| {
+            let mut keys = vec![];
+            t.for_each(&mut vec![], &mut |k, &v| keys.push((k.to_vec(), v)));
+            keys
+        };
+
+        let union = a.combine(&b, SetOp::Union);
+        assert!(union.is_compact());
+        assert_eq!(
+            keys(&union),
+            vec![
+                (vec![1, 2], 0),
+                (vec![1, 2, -3], 2),
+                (vec![1, 2, 3], 1),
+                (vec![1, 2, 5], 5),
+                (vec![9], 6),
+                (vec![9, 8, 7], 3),
+            ]
+        );
+        assert!(a.combines_any(&b, SetOp::Union));
+
+        let intersection = a.combine(&b, SetOp::Intersection);
+        assert!(intersection.is_compact());
+        assert_eq!(keys(&intersection), vec![(vec![1, 2, 3], 1)]);
+        assert_eq!(intersection.children()[0].key(), &[1, 2, 3]);
+
+        let difference = b.combine(&a, SetOp::Difference);
+        assert!(difference.is_compact());
+        assert_eq!(keys(&difference), vec![(vec![1, 2, 5], 5), (vec![9], 6)]);
+        assert!(b.combines_any(&a, SetOp::Difference));
+        assert!(!intersection.combines_any(&a, SetOp::Difference));
+        assert!(!a.combines_any(&Tree::empty(), SetOp::Intersection));
+    }
+
+    #[test]
+    fn test_diff() {
+        let a = sample_tree();
+        let mut b = sample_tree();
+        b.insert(&[1, 2, 3], 7);
+        b.insert(&[1, 2, 5], 5);
+        b.remove(&[9, 8, 7]);
+        b.insert(&[9], 6);
+        assert_eq!(
+            a.diff(&b).collect::<Vec<_>>(),
+            vec![
+                (vec![1, 2, 3], Some(&1), Some(&7)),
+                (vec![1, 2, 5], None, Some(&5)),
+                (vec![9], None, Some(&6)),
+                (vec![9, 8, 7], Some(&3), None),
+            ]
+        );
+        assert_eq!(a.diff(&a.clone()).next(), None);
+
+        let mut diff = a.diff(&b);
+        assert_eq!(diff.size_hint(), (0, Some(9)));
+        diff.next();
+        assert_eq!(diff.size_hint(), (0, Some(3)));
+        assert_eq!(a.diff(&a).size_hint(), (0, Some(8)));
+        assert_eq!(a.diff(&a).next(), None);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut root = sample_tree();
+        let mut other = Tree::empty();
+        other.insert(&[1, 2, 3], 10);
+        other.insert(&[1, 5], 11);
+        other.insert(&[9, 8], 12);
+        let mut collisions = vec![];
+        root.merge(other, &mut vec![], &mut |k, a, b| {
+            collisions.push(k.to_vec());
+            a + b
+        });
+        assert_eq!(collisions, vec![vec![1, 2, 3]]);
+        assert!(root.is_compact());
+        assert_eq!(root.count(), 6);
+        assert_eq!(root.find(&[1, 2, 3]).and_then(|x| x.value), Some(11));
+        assert_eq!(root.find(&[1, 5]).and_then(|x| x.value), Some(11));
+        assert_eq!(root.find(&[9, 8]).and_then(|x| x.value), Some(12));
+        assert_eq!(root.find(&[9, 8, 7]).and_then(|x| x.value), Some(3));
+    }
+
+    #[test]
+    fn test_split_off_below() {
+        let mut root = sample_tree();
+        let right = root.split_off_below(&[1, 2, 0]);
+        assert!(root.is_compact());
+        assert!(right.is_compact());
+        assert_eq!(root.count(), 2);
+        assert_eq!(right.count(), 2);
+        assert_eq!(root.children()[0].key(), &[1, 2]);
+        assert_eq!(root.children()[0].children()[0].key(), &[-3]);
+        assert_eq!(right.children()[0].key(), &[1, 2, 3]);
+        assert_eq!(right.children()[1].key(), &[9, 8, 7]);
+
+        let mut root = sample_tree();
+        let right = root.split_off_below(&[1]);
+        assert_eq!(root.count(), 0);
+        assert_eq!(right.count(), 4);
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut root = sample_tree();
+        let mut visited = vec![];
+        root.retain(&mut vec![], &mut |key, value| {
+            visited.push(key.to_vec());
+            *value += 10;
+            *value != 10 && *value != 12
+        });
+        assert_eq!(
+            visited,
+            vec![vec![1, 2], vec![1, 2, -3], vec![1, 2, 3], vec![9, 8, 7]]
+        );
+        assert!(root.is_compact());
+        assert_eq!(root.count(), 2);
+        assert_eq!(root.children()[0].key(), &[1, 2, 3]);
+        assert_eq!(root.children()[0].value(), Some(&11));
+        assert_eq!(root.children()[1].value(), Some(&13));
     }
 }